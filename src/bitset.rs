@@ -0,0 +1,110 @@
+use std::vec::Vec;
+use std::fmt::{Show, Formatter, Result};
+
+
+/// Number of bits in a word
+static BITS: uint = 64;
+
+
+/// A fixed size set of bits, packed into 64 bit words
+///
+/// Attributes
+///   * words - the packed bits, the last word is padded with zeros
+///   * len - number of bits in the set
+///
+#[deriving(Clone, Eq)]
+pub struct BitSet {
+  words: Vec<u64>,
+  len: uint
+}
+
+
+// Creation
+impl BitSet {
+  /// Creates a set of `len` bits, all cleared
+  pub fn new(len: uint) -> BitSet {
+    BitSet { words: Vec::from_elem((len + BITS - 1) / BITS, 0u64), len: len }
+  }
+  /// Creates a set of `len` bits, all set
+  pub fn full(len: uint) -> BitSet {
+    let mut set = BitSet::new(len);
+    for i in range(0, len) { set.set(i, true); }
+    set
+  }
+}
+
+
+// Getters
+impl BitSet {
+  /// Number of bits in the set
+  pub fn len(&self) -> uint {
+    self.len
+  }
+  /// Whether bit `i` is set
+  pub fn get(&self, i: uint) -> bool {
+    assert!(i < self.len)
+    (*self.words.get(i / BITS) >> (i % BITS)) & 1 == 1
+  }
+  /// Number of bits that are set
+  pub fn count(&self) -> uint {
+    self.words.iter().fold(0, |a, w| a + w.count_ones() as uint)
+  }
+  /// Iterator over the indices of the set bits
+  pub fn iter<'a>(&'a self) -> Bits<'a> {
+    Bits { set: self, i: 0 }
+  }
+}
+
+
+// Setters
+impl BitSet {
+  /// Sets or clears bit `i`
+  pub fn set(&mut self, i: uint, b: bool) {
+    assert!(i < self.len)
+    let mask = 1u64 << (i % BITS);
+    let word = self.words.get_mut(i / BITS);
+    if b { *word = *word | mask } else { *word = *word & !mask }
+  }
+}
+
+
+/// Iterator over the set bits of a BitSet
+pub struct Bits<'a> {
+  set: &'a BitSet,
+  i: uint
+}
+
+
+impl<'a> Iterator<uint> for Bits<'a> {
+  fn next(&mut self) -> Option<uint> {
+    while self.i < self.set.len {
+      let word = *self.set.words.get(self.i / BITS) >> (self.i % BITS);
+
+      // Skip the rest of an empty word
+      if word == 0 {
+        self.i = (self.i / BITS + 1) * BITS;
+        continue
+      }
+
+      let i = self.i;
+      self.i += 1;
+
+      if word & 1 == 1 { return Some(i) }
+    }
+
+    None
+  }
+}
+
+
+impl Show for BitSet {
+  fn fmt(&self, f: &mut Formatter) -> Result {
+    let mut buf = ~"";
+
+    for i in range(0, self.len) {
+      buf.push_str(if self.get(i) { "1, " } else { "0, " });
+    }
+
+    write!(f.buf, "{:s}", buf)
+  }
+}
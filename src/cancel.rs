@@ -0,0 +1,35 @@
+use std::vec::Vec;
+use std::sync::atomics::{AtomicBool, SeqCst};
+use sync::Arc;
+
+
+/// A flag that can be shared between tasks to ask a
+/// search to stop as soon as it can.
+///
+/// A token made with `child` is also cancelled when its
+/// parent is, but cancelling it leaves the parent alone.
+#[deriving(Clone)]
+pub struct CancelToken {
+  flags: Vec<Arc<AtomicBool>>
+}
+
+
+impl CancelToken {
+  pub fn new() -> CancelToken {
+    CancelToken { flags: Vec::from_elem(1, Arc::new(AtomicBool::new(false))) }
+  }
+  /// Creates a token that is cancelled along with this one
+  pub fn child(&self) -> CancelToken {
+    let mut flags = self.flags.clone();
+    flags.push(Arc::new(AtomicBool::new(false)));
+    CancelToken { flags: flags }
+  }
+  /// Asks every search holding the token to stop
+  pub fn cancel(&self) {
+    self.flags.last().unwrap().store(true, SeqCst);
+  }
+  /// Whether the token, or one of its parents, was cancelled
+  pub fn isCancelled(&self) -> bool {
+    self.flags.iter().any(|f| f.load(SeqCst))
+  }
+}
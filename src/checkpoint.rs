@@ -0,0 +1,168 @@
+use std::hash;
+use std::vec::Vec;
+use std::io::{File, fs};
+use std::sync::atomics::{AtomicUint, SeqCst};
+use sync::Arc;
+use time::precise_time_ns;
+
+
+/// First line of every checkpoint file
+static HEADER: &'static str = "pentominoes-checkpoint 2";
+
+
+/// Hashes the contents of a puzzle file, along with the options
+/// that change the matrix built from it, so a checkpoint is not
+/// resumed against a different search tree
+pub fn puzzleHash(contents: &[u8], options: &str) -> u64 {
+  hash::hash(&(contents, options))
+}
+
+
+/// A position in the search tree.
+///
+///   * `hash` - hash of the puzzle the search was run on
+///   * `found` - number of solutions found before the position
+///   * `tally` - a count kept by the caller (the number of non
+///               isometric solutions, when counting)
+///   * `output` - the length of the output file, in bytes, once the
+///                solutions found before the position were written
+///   * `advance` - whether the solution at the position has
+///                 already been passed on
+///   * `path` - the row chosen at each level of the search
+#[deriving(Clone, Eq, Show)]
+pub struct Checkpoint {
+  pub hash: u64,
+  pub found: uint,
+  pub tally: uint,
+  pub output: uint,
+  pub advance: bool,
+  pub path: Vec<uint>
+}
+
+
+impl Checkpoint {
+  /// Reads a checkpoint written by `save`
+  pub fn load(path: &Path) -> Checkpoint {
+    let contents = match File::open(path).read_to_str() {
+      Ok(s) => s,
+      Err(e) => fail!("checkpoint error: {}", e)
+    };
+
+    let mut lines = contents.lines();
+
+    if lines.next() != Some(HEADER) { fail!("not a checkpoint file!") }
+
+    let mut checkpoint = Checkpoint {
+      hash: 0, found: 0, tally: 0, output: 0, advance: false, path: Vec::new()
+    };
+
+    for line in lines {
+      let mut words = line.words();
+      let key = words.next();
+      let values: Vec<u64> = words.map(|w| match from_str(w) {
+        Some(v) => v,
+        None => fail!("invalid checkpoint value `{:s}`", w)
+      }).collect();
+
+      let value = || -> u64 {
+        if values.len() != 1 { fail!("invalid checkpoint line `{:s}`", line) }
+        *values.get(0)
+      };
+
+      match key {
+        Some("hash") => checkpoint.hash = value(),
+        Some("found") => checkpoint.found = value() as uint,
+        Some("tally") => checkpoint.tally = value() as uint,
+        Some("output") => checkpoint.output = value() as uint,
+        Some("advance") => checkpoint.advance = value() == 1,
+        Some("path") => checkpoint.path = values.iter().map(|r| *r as uint).collect(),
+        Some(_) => fail!("invalid checkpoint line `{:s}`", line),
+        None => ()
+      }
+    }
+
+    checkpoint
+  }
+  /// Writes the checkpoint to a temporary file next to `path`,
+  /// then moves it into place, so an old checkpoint is never
+  /// left half overwritten
+  pub fn save(&self, path: &Path) {
+    let temp = path.with_extension("tmp");
+    let mut buf = ~"";
+
+    buf.push_str(format!("{:s}\n", HEADER));
+    buf.push_str(format!("hash {}\n", self.hash));
+    buf.push_str(format!("found {:u}\n", self.found));
+    buf.push_str(format!("tally {:u}\n", self.tally));
+    buf.push_str(format!("output {:u}\n", self.output));
+    buf.push_str(format!("advance {:u}\n", if self.advance { 1u } else { 0 }));
+    buf.push_str("path");
+
+    for r in self.path.iter() { buf.push_str(format!(" {:u}", *r)); }
+
+    buf.push_str("\n");
+
+    let result = File::create(&temp).write_str(buf).and_then(|_| fs::rename(&temp, path));
+
+    match result {
+      Ok(_) => (),
+      Err(e) => warn!("could not save checkpoint: {}", e)
+    }
+  }
+}
+
+
+/// Saves the position of a search to a file every so often
+///
+///   * `path` - the checkpoint file
+///   * `interval` - nanoseconds between checkpoints
+///   * `hash` - hash of the puzzle being searched
+///   * `tally` - the caller's count, saved with each checkpoint
+///   * `output` - the length of the output so far, saved with each
+///                checkpoint
+///   * `last` - when the last checkpoint was saved
+pub struct Checkpointer {
+  path: Path,
+  interval: u64,
+  hash: u64,
+  tally: Arc<AtomicUint>,
+  output: Arc<AtomicUint>,
+  last: u64
+}
+
+
+impl Checkpointer {
+  pub fn new(path: Path, seconds: uint, hash: u64, tally: Arc<AtomicUint>,
+             output: Arc<AtomicUint>) -> Checkpointer {
+    Checkpointer {
+      path: path,
+      interval: seconds as u64 * 1000000000,
+      hash: hash,
+      tally: tally,
+      output: output,
+      last: precise_time_ns()
+    }
+  }
+  /// Whether it is time to save another checkpoint
+  pub fn due(&self) -> bool {
+    precise_time_ns() - self.last >= self.interval
+  }
+  /// Saves the position given by `path`
+  pub fn save(&mut self, found: uint, advance: bool, path: Vec<uint>) {
+    let checkpoint = Checkpoint {
+      hash: self.hash,
+      found: found,
+      tally: self.tally.load(SeqCst),
+      output: self.output.load(SeqCst),
+      advance: advance,
+      path: path
+    };
+
+    checkpoint.save(&self.path);
+    self.last = precise_time_ns();
+  }
+  /// Removes the checkpoint file once the search is complete
+  pub fn finish(&self) {
+    let _ = fs::unlink(&self.path);
+  }
+}
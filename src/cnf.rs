@@ -0,0 +1,192 @@
+use std::vec::Vec;
+use std::io::File;
+use solve::{Placement, Columns};
+
+
+/// How a limit on the number of true variables is written as clauses
+#[deriving(Eq, Show)]
+pub enum Encoding {
+  /// A clause for every pair of variables. Only used for at most one,
+  /// other limits fall back to the sequential counter.
+  Pairwise,
+  /// Sinz's sequential counter, which adds variables to count how
+  /// many are true so far, and a linear number of clauses
+  Sequential
+}
+
+
+/// A SAT instance in conjunctive normal form. Variables are numbered
+/// from 1, and a negative literal is the negation of a variable.
+///
+///   * `vars` - number of variables
+///   * `clauses` - literals of which at least one must be true
+pub struct Cnf {
+  pub vars: uint,
+  pub clauses: Vec<Vec<int>>
+}
+
+
+impl Cnf {
+  /// Creates an instance with `vars` variables and no clauses
+  pub fn new(vars: uint) -> Cnf {
+    Cnf { vars: vars, clauses: Vec::new() }
+  }
+  /// Adds a new variable, returning it
+  fn newVar(&mut self) -> int {
+    self.vars += 1;
+    self.vars as int
+  }
+  fn add(&mut self, clause: Vec<int>) {
+    self.clauses.push(clause);
+  }
+  /// Requires at most `k` of the literals to be true
+  pub fn atMost(&mut self, lits: &Vec<int>, k: uint, encoding: Encoding) {
+    let n = lits.len();
+
+    if n <= k { return }
+
+    if k == 0 {
+      for l in lits.iter() { self.add(Vec::from_elem(1, -*l)); }
+      return
+    }
+
+    if k == 1 && encoding == Pairwise {
+      for i in range(0, n) {
+        for j in range(i + 1, n) {
+          self.add(Vec::from_slice([-*lits.get(i), -*lits.get(j)]));
+        }
+      }
+      return
+    }
+
+    // s[i][j] is true if at least j + 1 of the first i + 1 literals are
+    let mut s = Vec::from_fn(n - 1, |_| Vec::from_elem(k, 0i));
+
+    for i in range(0, n - 1) {
+      for j in range(0, k) { *s.get_mut(i).get_mut(j) = self.newVar(); }
+    }
+
+    let x = |i: uint| *lits.get(i);
+
+    self.add(Vec::from_slice([-x(0), *s.get(0).get(0)]));
+    for j in range(1, k) { self.add(Vec::from_elem(1, -*s.get(0).get(j))); }
+
+    for i in range(1, n - 1) {
+      self.add(Vec::from_slice([-x(i), *s.get(i).get(0)]));
+      self.add(Vec::from_slice([-*s.get(i - 1).get(0), *s.get(i).get(0)]));
+
+      for j in range(1, k) {
+        self.add(Vec::from_slice([-x(i), -*s.get(i - 1).get(j - 1), *s.get(i).get(j)]));
+        self.add(Vec::from_slice([-*s.get(i - 1).get(j), *s.get(i).get(j)]));
+      }
+
+      self.add(Vec::from_slice([-x(i), -*s.get(i - 1).get(k - 1)]));
+    }
+
+    self.add(Vec::from_slice([-x(n - 1), -*s.get(n - 2).get(k - 1)]));
+  }
+  /// Requires at least `k` of the literals to be true, by allowing
+  /// at most `n - k` of them to be false
+  pub fn atLeast(&mut self, lits: &Vec<int>, k: uint, encoding: Encoding) {
+    if k == 0 { return }
+
+    if k > lits.len() {
+      // No way to pick enough, so add a clause that can't be met
+      let v = self.newVar();
+      self.add(Vec::from_elem(1, v));
+      self.add(Vec::from_elem(1, -v));
+    } else if k == 1 {
+      self.add(lits.clone());
+    } else {
+      let negated = lits.iter().map(|l| -*l).collect();
+      self.atMost(&negated, lits.len() - k, encoding);
+    }
+  }
+  /// Writes the instance in the DIMACS format
+  pub fn save(&self, path: &Path, comment: &str) {
+    let mut buf = format!("c {:s}\np cnf {:u} {:u}\n", comment, self.vars, 
+                          self.clauses.len());
+
+    for clause in self.clauses.iter() {
+      for l in clause.iter() { buf.push_str(format!("{} ", *l)); }
+      buf.push_str("0\n");
+    }
+
+    match File::create(path).write_str(buf) {
+      Ok(_) => (),
+      Err(e) => fail!("CNF file error: {}", e)
+    }
+  }
+}
+
+
+/// Encodes the placements as a SAT instance, with variable `i + 1`
+/// true if placement `i` is used. Each square of the board is covered
+/// by exactly one placement, each piece is used at most as many times
+/// as it has copies (exactly, if it is mandatory), and each group of
+/// pieces is used between its least and most number of times.
+pub fn encodePlacements(placements: &Vec<Placement>, cols: &Columns,
+                        encoding: Encoding) -> Cnf {
+  let mut cnf = Cnf::new(placements.len());
+  let mut covering: Vec<Vec<int>> = Vec::from_fn(cols.len(), |_| Vec::new());
+
+  for (i, p) in placements.iter().enumerate() {
+    for c in p.filled().iter() { covering.get_mut(*c).push(i as int + 1); }
+  }
+
+  for c in range(0, cols.len()) {
+    let lits = covering.get(c);
+    let capacity = cols.capacity(c);
+
+    if cols.primary().get(c) { cnf.atLeast(lits, capacity, encoding); }
+    cnf.atMost(lits, capacity, encoding);
+  }
+
+  for &(ref group, min, max) in cols.groups().iter() {
+    let mut lits = Vec::new();
+    for c in group.iter() { lits.push_all(covering.get(*c).as_slice()); }
+
+    cnf.atLeast(&lits, min, encoding);
+    cnf.atMost(&lits, max, encoding);
+  }
+
+  cnf
+}
+
+
+/// Reads the placements used by a model that a SAT solver found for
+/// an instance made by `encodePlacements`. Both the competition format
+/// (`s SATISFIABLE` then lines of `v` literals) and a bare list of
+/// literals are read. Fails if the solver found the instance can not
+/// be satisfied.
+pub fn loadModel(path: &Path, placements: uint) -> Vec<uint> {
+  let contents = match File::open(path).read_to_str() {
+    Ok(s) => s,
+    Err(e) => fail!("model file error: {}", e)
+  };
+
+  let mut solution = Vec::new();
+
+  for line in contents.lines() {
+    let skip = match line.words().next() {
+      Some("c") => continue,
+      Some(w) if w.starts_with("UNSAT") || (w == "s" && line.contains("UNSAT")) => {
+        fail!("the SAT solver found no solution!")
+      }
+      Some("s") | Some("SAT") | Some("SATISFIABLE") => continue,
+      Some("v") => 1,
+      _ => 0
+    };
+
+    for w in line.words().skip(skip) {
+      let l: int = match from_str(w) {
+        Some(l) => l,
+        None => fail!("invalid literal `{:s}` in model file!", w)
+      };
+
+      if l > 0 && l as uint <= placements { solution.push(l as uint - 1); }
+    }
+  }
+
+  solution
+}
@@ -0,0 +1,99 @@
+use std::uint;
+use std::vec::Vec;
+use parse::Rules;
+use pentomino::Pentomino;
+
+
+/// Colour of the characters that match any colour
+static WILDCARD: uint = uint::MAX;
+
+/// Number of ASCII characters
+static CHARS: uint = 128;
+
+
+/// Which characters of the board and the pieces match each other,
+/// from the colour legend of a puzzle file.
+///
+/// Without a legend, each character is a colour of its own, so a
+/// square of a piece can only cover a square of the board drawn with
+/// the same character. A legend gives characters a named colour, so
+/// different characters can match (`; colour a dark`, `; colour A dark`),
+/// and wildcards match every colour.
+///
+/// Pieces are two sided. A square of a piece shows the same character
+/// on both sides unless the legend says otherwise (`; back a b`), so
+/// turning a piece over can change its colours.
+///
+/// Attributes
+///   * colours - the colour of each character. Characters with a named
+///               colour come after the characters themselves.
+///   * backs - the character each character shows once turned over
+///   * legend - whether the puzzle file has a legend at all
+pub struct Palette {
+  colours: Vec<uint>,
+  backs: Vec<char>,
+  legend: bool
+}
+
+
+impl Palette {
+  /// Makes the palette from the colours, wildcards and backs stated
+  /// in the rules of a puzzle file
+  pub fn new(rules: &Rules) -> Palette {
+    let mut colours = Vec::from_fn(CHARS, |c| c);
+    let mut backs = Vec::from_fn(CHARS, |c| c as u8 as char);
+    let mut names: Vec<&str> = Vec::new();
+
+    for rule in rules.colours.iter() {
+      let colour = match names.iter().position(|n| *n == rule.colour.as_slice()) {
+        Some(i) => i,
+        None => { names.push(rule.colour.as_slice()); names.len() - 1 }
+      };
+
+      *colours.get_mut(index(rule.square)) = CHARS + colour;
+    }
+
+    for c in rules.wildcards.iter() { *colours.get_mut(index(*c)) = WILDCARD; }
+    for &(front, back) in rules.backs.iter() { *backs.get_mut(index(front)) = back; }
+
+    let legend = rules.colours.len() + rules.wildcards.len() + rules.backs.len() > 0;
+
+    Palette { colours: colours, backs: backs, legend: legend }
+  }
+  /// Whether the puzzle file gives a legend, so that squares are
+  /// told apart by their colour rather than just by being filled
+  pub fn isActive(&self) -> bool {
+    self.legend
+  }
+  /// Whether two squares of the board have the same colour, so that
+  /// every piece that can cover one can cover the other
+  pub fn sameColour(&self, a: Ascii, b: Ascii) -> bool {
+    *self.colours.get(a.to_byte() as uint) == *self.colours.get(b.to_byte() as uint)
+  }
+  /// Whether a square of a piece drawn with `piece` can cover
+  /// a square of the board drawn with `board`
+  pub fn matches(&self, board: Ascii, piece: Ascii) -> bool {
+    let b = *self.colours.get(board.to_byte() as uint);
+    let p = *self.colours.get(piece.to_byte() as uint);
+
+    b == WILDCARD || p == WILDCARD || b == p
+  }
+  /// Turns a piece over, reflecting it over the X axis and
+  /// showing the back of each square
+  pub fn turnOver(&self, piece: &Pentomino) -> Pentomino {
+    piece.reflectX().recolour(|c| self.backs.get(c.to_byte() as uint).to_ascii())
+  }
+  /// Turns a piece over (if `reflected` is set), then rotates
+  /// it right 90 degrees `turns` times
+  pub fn orient(&self, piece: &Pentomino, turns: uint, reflected: bool) -> Pentomino {
+    let piece = if reflected { self.turnOver(piece) } else { piece.clone() };
+    piece.orient(turns, false)
+  }
+}
+
+
+/// Index of a character of the legend, which must be ASCII
+fn index(c: char) -> uint {
+  if !c.is_ascii() { fail!("colour legend character `{}` is not ASCII!", c) }
+  c as uint
+}
@@ -0,0 +1,189 @@
+use std::vec::Vec;
+use bitset::BitSet;
+use parse::Marks;
+use colour::Palette;
+use pentomino::{Pentomino, Filled, Empty};
+use prune::{Prune, RegionPrune, ColourPrune, Colouring};
+use solve::{Placement, Columns, Pin, orientations, buildMatrix};
+
+
+/// Why one way of putting a piece over a square does not fit
+#[deriving(Eq)]
+enum Blocker {
+  /// The piece sticks out of the board
+  OffBoard,
+  /// The piece needs the square `x`, `y`, which is a hole
+  Hole(uint, uint),
+  /// The piece needs the square `x`, `y`, which is another colour
+  Colour(uint, uint),
+  /// The piece needs the square `x`, `y`, which it may not cover
+  Forbidden(uint, uint)
+}
+
+
+/// Finds what stops a piece, turned to `orientation`, from covering
+/// the square `x`, `y` with its own square `sx`, `sy`
+fn blockers(board: &Pentomino, orientation: &Pentomino, palette: &Palette,
+            forbidden: &BitSet, x: uint, y: uint, sx: uint, sy: uint) -> Vec<Blocker> {
+  let mut found = Vec::new();
+
+  for (x0, y0, c) in orientation.filled() {
+    let bx = x as int - sx as int + x0 as int;
+    let by = y as int - sy as int + y0 as int;
+
+    // Squares to the left of or above the board wrap around to
+    // indices that are too large, so they are off the board too
+    let blocker = match board.get_opt(bx as uint, by as uint) {
+      None => OffBoard,
+      Some(&Empty) => Hole(bx as uint, by as uint),
+      Some(&Filled(b)) if !palette.matches(b, c) => Colour(bx as uint, by as uint),
+      Some(_) if forbidden.get(board.getIndex(bx as uint, by as uint)) => {
+        Forbidden(bx as uint, by as uint)
+      }
+      Some(_) => continue
+    };
+
+    if !found.contains(&blocker) { found.push(blocker); }
+  }
+
+  found
+}
+
+
+/// Explains why no placement covers the square `x`, `y`, by finding
+/// what stops every piece that could be put over it
+fn explainSquare(board: &Pentomino, pentominoes: &Vec<Pentomino>, palette: &Palette,
+                 pins: &Vec<Pin>, forbidden: &Vec<BitSet>, useRotations: bool, 
+                 useReflections: bool, x: uint, y: uint) -> ~str {
+  // What stops every way of covering the square
+  let mut common: Option<Vec<Blocker>> = None;
+
+  for (i, piece) in pentominoes.iter().enumerate() {
+    // A pinned piece only has the one placement
+    if pins.iter().any(|p| p.piece == i) { continue }
+
+    for orientation in orientations(piece, palette, useRotations, useReflections).iter() {
+      for (sx, sy, _) in orientation.filled() {
+        let found = blockers(board, orientation, palette, forbidden.get(i), x, y, sx, sy);
+
+        common = match common {
+          Some(mut c) => { c.retain(|b| found.contains(b)); Some(c) }
+          None => Some(found)
+        };
+      }
+    }
+  }
+
+  let common = match common {
+    Some(c) => c,
+    None => return format!("cell ({:u},{:u}) can not be covered, since every piece is pinned elsewhere", x, y)
+  };
+
+  // Name a square the pieces need if there is one, since that
+  // is the most useful thing to change
+  for b in common.iter() {
+    match *b {
+      Colour(bx, by) if bx == x && by == y => {
+        return format!("cell ({:u},{:u}) has a colour that no piece has", x, y)
+      }
+      Forbidden(bx, by) if bx == x && by == y => {
+        return format!("cell ({:u},{:u}) is forbidden to every piece that could cover it", x, y)
+      }
+      Hole(bx, by) => {
+        return format!("cell ({:u},{:u}) can only be covered by pieces that also need ({:u},{:u}), which is a hole",
+                       x, y, bx, by)
+      }
+      Colour(bx, by) => {
+        return format!("cell ({:u},{:u}) can only be covered by pieces that also need ({:u},{:u}), which is the wrong colour for them",
+                       x, y, bx, by)
+      }
+      Forbidden(bx, by) => {
+        return format!("cell ({:u},{:u}) can only be covered by pieces that also need ({:u},{:u}), which they may not cover",
+                       x, y, bx, by)
+      }
+      OffBoard => ()
+    }
+  }
+
+  if common.contains(&OffBoard) {
+    format!("cell ({:u},{:u}) can only be covered by pieces that stick out of the board", x, y)
+  } else {
+    format!("cell ({:u},{:u}) can not be covered: every piece over it runs into a hole, the edge of the board, or a square it may not cover",
+            x, y)
+  }
+}
+
+
+/// Looks for simple reasons a puzzle has no solutions, returning a
+/// short description of each one found:
+///
+///   * the pieces have too few squares to cover the board, or the
+///     mandatory pieces have too many
+///   * pieces that no placement fits, and whether the puzzle needs them
+///   * squares of the board that no placement covers
+///   * the black and white squares of the board (coloured like a
+///     checkerboard) can not be matched by the pieces
+///   * the board is split into regions that the pieces can not fill
+pub fn diagnose(board: &Pentomino, pentominoes: &Vec<Pentomino>, marks: &Vec<Marks>,
+                palette: &Palette, pins: &Vec<Pin>, forbidden: &Vec<BitSet>, cols: &Columns,
+                placements: &Vec<Placement>, useRotations: bool,
+                useReflections: bool) -> Vec<~str> {
+  let offset = pentominoes.len();
+  let mut reasons = Vec::new();
+
+  let total = pentominoes.iter().zip(marks.iter()).fold(0, |a, (p, m)| a + p.size() * m.copies);
+  let mandatory = pentominoes.iter().zip(marks.iter()).fold(0, |a, (p, m)| {
+    if m.mandatory { a + p.size() * m.copies } else { a }
+  });
+
+  if total < board.size() {
+    reasons.push(format!("the board has {:u} squares, but the pieces only have {:u}",
+                         board.size(), total));
+  }
+
+  if mandatory > board.size() {
+    reasons.push(format!("the board has {:u} squares, but the mandatory pieces have {:u}",
+                         board.size(), mandatory));
+  }
+
+  for (i, (piece, m)) in pentominoes.iter().zip(marks.iter()).enumerate() {
+    if cols.count(i) > 0 { continue }
+
+    let reason = if m.mandatory {
+      "must be used, but fits nowhere on the board"
+    } else if total - piece.size() * m.copies < board.size() {
+      "fits nowhere on the board, but the other pieces are too small to cover it without it"
+    } else {
+      "fits nowhere on the board, so it is never used"
+    };
+
+    reasons.push(format!("piece {:u} {:s}:\n{:s}", i, reason, piece.to_str()));
+  }
+
+  for (x, y, _) in board.filled() {
+    if cols.count(offset + board.getIndex(x, y)) == 0 {
+      reasons.push(explainSquare(board, pentominoes, palette, pins, forbidden,
+                                 useRotations, useReflections, x, y));
+    }
+  }
+
+  // The checks made while searching, run on the whole board
+  let matrix = buildMatrix(placements, cols);
+  let checkerboard = Colouring::checkerboard();
+  let mut parity = ColourPrune::new(board, pentominoes, cols.primary(), 
+                                    Colouring::checkerboard());
+  let mut regions = RegionPrune::new(board, pentominoes);
+
+  if parity.prune(&matrix) {
+    let black = board.filled().filter(|&(x, y, _)| checkerboard.colour(x, y) == 0).count();
+
+    reasons.push(format!("the pieces can not cover the {:u} black and {:u} white squares of the board, coloured like a checkerboard",
+                         black, board.size() - black));
+  }
+
+  if regions.prune(&matrix) {
+    reasons.push(~"the board is split into regions that no set of the pieces can fill exactly");
+  }
+
+  reasons
+}
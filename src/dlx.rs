@@ -0,0 +1,416 @@
+use std::cmp;
+use std::uint;
+use std::vec::Vec;
+use bitset::BitSet;
+
+
+/// Index of the root header in a Matrix
+static ROOT: uint = 0;
+
+/// Row index used for header nodes
+static HEADER: uint = uint::MAX;
+
+
+/// A set of columns that must be covered, between them, at 
+/// least `min` and at most `max` times
+#[deriving(Clone)]
+struct Group {
+  headers: Vec<uint>,
+  min: uint,
+  max: uint
+}
+
+
+/// A sparse exact cover matrix using Dancing Links.
+///
+/// Every node lives in a set of parallel vectors and refers to its
+/// neighbours by index. Node 0 is the root, nodes 1 through `n` are
+/// the column headers, and every node after that is part of a row.
+///
+/// Attributes
+///   * left, right - horizontal links (headers link to the root,
+///                   row nodes link to the rest of their row)
+///   * up, down - vertical links between the nodes of a column
+///   * col - the column header a node belongs to
+///   * row - the row a node belongs to
+///   * len - number of rows currently linked under each header
+///   * capacity - number of rows each column can be covered by
+///   * remaining - number of rows each column can still be covered by
+///   * groups - limits on how many times sets of columns are covered,
+///              checked at every node instead of branched on
+///   * rows - the first node of each row
+///
+#[deriving(Clone)]
+pub struct Matrix {
+  left: Vec<uint>,
+  right: Vec<uint>,
+  up: Vec<uint>,
+  down: Vec<uint>,
+  col: Vec<uint>,
+  row: Vec<uint>,
+  len: Vec<uint>,
+  capacity: Vec<uint>,
+  remaining: Vec<uint>,
+  groups: Vec<Group>,
+  rows: Vec<uint>
+}
+
+
+// Creation
+impl Matrix {
+  /// Creates an empty matrix with a header for each column. Only
+  /// primary columns are linked to the root, and must be covered
+  /// exactly once. Secondary columns may be covered at most once.
+  pub fn new(primary: &BitSet) -> Matrix {
+    let headers = primary.len() + 1;
+    let mut matrix = Matrix {
+      left: Vec::from_fn(headers, |i| i),
+      right: Vec::from_fn(headers, |i| i),
+      up: Vec::from_fn(headers, |i| i),
+      down: Vec::from_fn(headers, |i| i),
+      col: Vec::from_fn(headers, |i| i),
+      row: Vec::from_elem(headers, HEADER),
+      len: Vec::from_elem(headers, 0 as uint),
+      capacity: Vec::from_elem(headers, 1 as uint),
+      remaining: Vec::from_elem(headers, 1 as uint),
+      groups: Vec::new(),
+      rows: Vec::new()
+    };
+
+    for i in primary.iter() {
+      let h = i + 1;
+      let last = *matrix.left.get(ROOT);
+
+      *matrix.left.get_mut(h) = last;
+      *matrix.right.get_mut(h) = ROOT;
+      *matrix.right.get_mut(last) = h;
+      *matrix.left.get_mut(ROOT) = h;
+    }
+
+    matrix
+  }
+  /// Appends a row which has a one in each of the given columns
+  pub fn addRow(&mut self, columns: &Vec<uint>) {
+    let r = self.rows.len();
+    let first = self.col.len();
+
+    for (i, c) in columns.iter().enumerate() {
+      let n = self.col.len();
+      let h = *c + 1;
+      let above = *self.up.get(h);
+
+      self.col.push(h);
+      self.row.push(r);
+      self.up.push(above);
+      self.down.push(h);
+      *self.down.get_mut(above) = n;
+      *self.up.get_mut(h) = n;
+      *self.len.get_mut(h) += 1;
+
+      if i == 0 {
+        self.left.push(n);
+        self.right.push(n);
+      } else {
+        let last = *self.left.get(first);
+
+        self.left.push(last);
+        self.right.push(first);
+        *self.right.get_mut(last) = n;
+        *self.left.get_mut(first) = n;
+      }
+    }
+
+    self.rows.push(first);
+  }
+  /// Lets column `c` be covered by up to `n` rows instead of one. 
+  /// A primary column must then be covered by exactly `n` rows,
+  /// and is no longer chosen to branch on, since trying its rows 
+  /// in every order would find each solution many times.
+  pub fn setCapacity(&mut self, c: uint, n: uint) {
+    let h = c + 1;
+
+    *self.capacity.get_mut(h) = n;
+    *self.remaining.get_mut(h) = n;
+
+    let l = *self.left.get(h);
+    let r = *self.right.get(h);
+
+    if n > 1 && l != h {
+      *self.right.get_mut(l) = r;
+      *self.left.get_mut(r) = l;
+      *self.left.get_mut(h) = h;
+      *self.right.get_mut(h) = h;
+      self.groups.push(Group { headers: Vec::from_elem(1, h), min: n, max: n });
+    }
+  }
+  /// Requires the given columns to be covered, between them, at 
+  /// least `min` and at most `max` times
+  pub fn addGroup(&mut self, columns: &Vec<uint>, min: uint, max: uint) {
+    let headers = columns.iter().map(|c| *c + 1).collect();
+    self.groups.push(Group { headers: headers, min: min, max: max });
+  }
+}
+
+
+// Getters
+impl Matrix {
+  /// The root header, which the uncovered primary columns 
+  /// are linked to
+  pub fn root(&self) -> uint { ROOT }
+  /// Number of rows in the matrix
+  pub fn numRows(&self) -> uint { self.rows.len() }
+  /// Number of headers in the matrix, counting the root
+  pub fn numHeaders(&self) -> uint { self.len.len() }
+  /// Whether any set of columns has limits on how many
+  /// times it is covered
+  pub fn hasGroups(&self) -> bool { self.groups.len() > 0 }
+  /// Number of rows currently linked under a column header
+  pub fn len(&self, c: uint) -> uint { *self.len.get(c) }
+  /// The node below `n`
+  pub fn down(&self, n: uint) -> uint { *self.down.get(n) }
+  /// The node after `n` in its row
+  pub fn right(&self, n: uint) -> uint { *self.right.get(n) }
+  /// The header of the column that node `n` is in
+  pub fn col(&self, n: uint) -> uint { *self.col.get(n) }
+  /// The row that node `n` is part of
+  pub fn row(&self, n: uint) -> uint { *self.row.get(n) }
+  /// The header of column `c`
+  pub fn header(&self, c: uint) -> uint { c + 1 }
+  /// Number of rows that can still cover the column of header `h`,
+  /// which is zero once it has been covered
+  pub fn remaining(&self, h: uint) -> uint { *self.remaining.get(h) }
+  /// Number of rows that can cover the column of header `h`
+  pub fn capacity(&self, h: uint) -> uint { *self.capacity.get(h) }
+  /// The first node of row `r`
+  pub fn rowNode(&self, r: uint) -> uint { *self.rows.get(r) }
+  /// The node of row `r` in column `c`, if the row has a one there
+  pub fn nodeIn(&self, r: uint, c: uint) -> Option<uint> {
+    let first = self.rowNode(r);
+    let mut j = first;
+
+    loop {
+      if *self.col.get(j) == c { return Some(j) }
+      j = *self.right.get(j);
+      if j == first { return None }
+    }
+  }
+  /// Whether a group of columns has been covered too many times, 
+  /// or has too few rows left to be covered enough times, so that 
+  /// no solution can be found below the current node
+  pub fn blocked(&self) -> bool {
+    self.groups.iter().any(|g| {
+      let mut used = 0;
+      let mut available = 0;
+
+      for h in g.headers.iter() {
+        let remaining = *self.remaining.get(*h);
+        used += *self.capacity.get(*h) - remaining;
+        available += cmp::min(remaining, self.len(*h));
+      }
+
+      used > g.max || used + available < g.min
+    })
+  }
+  /// Whether every group of columns has been covered enough times,
+  /// which a solution needs on top of covering the primary columns
+  pub fn satisfied(&self) -> bool {
+    self.groups.iter().all(|g| {
+      let used = g.headers.iter().fold(0, |a, h| {
+        a + *self.capacity.get(*h) - *self.remaining.get(*h)
+      });
+
+      used >= g.min
+    })
+  }
+  /// Chooses the column with bounds (a group of one column) that has
+  /// not been covered its least number of times yet and has the fewest 
+  /// rows, or None if there is no such column. Bounded columns are not 
+  /// linked to the root, so this is only branched on once every 
+  /// primary column has been covered.
+  pub fn unmet(&self) -> Option<uint> {
+    let mut min = None;
+    let mut minLen = uint::MAX;
+
+    for g in self.groups.iter().filter(|g| g.headers.len() == 1) {
+      let h = *g.headers.get(0);
+
+      if *self.capacity.get(h) - *self.remaining.get(h) < g.min && self.len(h) < minLen {
+        min = Some(h);
+        minLen = self.len(h);
+      }
+    }
+
+    min
+  }
+  /// Chooses the uncovered primary column with the fewest rows,
+  /// or None if every primary column has been covered
+  pub fn choose(&self) -> Option<uint> {
+    let mut min = None;
+    let mut minLen = uint::MAX;
+    let mut c = *self.right.get(ROOT);
+
+    while c != ROOT {
+      if self.len(c) < minLen {
+        min = Some(c);
+        minLen = self.len(c);
+      }
+      c = *self.right.get(c);
+    }
+
+    min
+  }
+}
+
+
+// Dancing
+impl Matrix {
+  /// Removes a column header, and every row that has
+  /// a one in that column
+  fn cover(&mut self, c: uint) {
+    let l = *self.left.get(c);
+    let r = *self.right.get(c);
+
+    *self.right.get_mut(l) = r;
+    *self.left.get_mut(r) = l;
+
+    let mut i = *self.down.get(c);
+
+    while i != c {
+      let mut j = *self.right.get(i);
+
+      while j != i {
+        let u = *self.up.get(j);
+        let d = *self.down.get(j);
+
+        *self.down.get_mut(u) = d;
+        *self.up.get_mut(d) = u;
+        *self.len.get_mut(*self.col.get(j)) -= 1;
+
+        j = *self.right.get(j);
+      }
+
+      i = *self.down.get(i);
+    }
+  }
+  /// Reverses `cover`. Must be called in the opposite
+  /// order that columns were covered.
+  fn uncover(&mut self, c: uint) {
+    let mut i = *self.up.get(c);
+
+    while i != c {
+      let mut j = *self.left.get(i);
+
+      while j != i {
+        let u = *self.up.get(j);
+        let d = *self.down.get(j);
+
+        *self.down.get_mut(u) = j;
+        *self.up.get_mut(d) = j;
+        *self.len.get_mut(*self.col.get(j)) += 1;
+
+        j = *self.left.get(j);
+      }
+
+      i = *self.up.get(i);
+    }
+
+    let l = *self.left.get(c);
+    let r = *self.right.get(c);
+
+    *self.right.get_mut(l) = c;
+    *self.left.get_mut(r) = c;
+  }
+  /// Selects the row containing node `n`, covering every column
+  /// the row has a one in. A column that can be covered by more 
+  /// than one row is only covered once it has used up its rows.
+  pub fn select(&mut self, n: uint) {
+    let mut j = n;
+
+    loop {
+      let c = *self.col.get(j);
+      if *self.remaining.get(c) == 1 { self.cover(c); }
+      *self.remaining.get_mut(c) -= 1;
+      j = *self.right.get(j);
+      if j == n { break }
+    }
+  }
+  /// Removes the row containing node `n` from every column it has
+  /// a one in, so it can no longer be chosen. Used when branching on
+  /// a column that can be covered by more than one row, so the rows
+  /// tried before are not chosen again further down the tree.
+  pub fn hide(&mut self, n: uint) {
+    let mut j = n;
+
+    loop {
+      let u = *self.up.get(j);
+      let d = *self.down.get(j);
+
+      *self.down.get_mut(u) = d;
+      *self.up.get_mut(d) = u;
+      *self.len.get_mut(*self.col.get(j)) -= 1;
+
+      j = *self.right.get(j);
+      if j == n { break }
+    }
+  }
+  /// Reverses `hide`
+  pub fn unhide(&mut self, n: uint) {
+    let mut j = *self.left.get(n);
+
+    loop {
+      let u = *self.up.get(j);
+      let d = *self.down.get(j);
+
+      *self.down.get_mut(u) = j;
+      *self.up.get_mut(d) = j;
+      *self.len.get_mut(*self.col.get(j)) += 1;
+
+      if j == n { break }
+      j = *self.left.get(j);
+    }
+  }
+  /// Unlinks the headers of the uncovered primary columns that are
+  /// not in `headers` from the root, so only the columns in it are
+  /// chosen from. Returns the headers that were unlinked, to pass
+  /// to `unfocus` before the matrix is changed any further.
+  pub fn focus(&mut self, headers: &BitSet) -> Vec<uint> {
+    let mut unlinked = Vec::new();
+    let mut c = *self.right.get(ROOT);
+
+    while c != ROOT {
+      if !headers.get(c) {
+        let l = *self.left.get(c);
+        let r = *self.right.get(c);
+
+        *self.right.get_mut(l) = r;
+        *self.left.get_mut(r) = l;
+        unlinked.push(c);
+      }
+      c = *self.right.get(c);
+    }
+
+    unlinked
+  }
+  /// Reverses `focus`
+  pub fn unfocus(&mut self, unlinked: &Vec<uint>) {
+    for c in unlinked.iter().rev() {
+      let l = *self.left.get(*c);
+      let r = *self.right.get(*c);
+
+      *self.right.get_mut(l) = *c;
+      *self.left.get_mut(r) = *c;
+    }
+  }
+  /// Reverses `select`
+  pub fn unselect(&mut self, n: uint) {
+    let mut j = *self.left.get(n);
+
+    loop {
+      let c = *self.col.get(j);
+      *self.remaining.get_mut(c) += 1;
+      if *self.remaining.get(c) == 1 { self.uncover(c); }
+      if j == n { break }
+      j = *self.left.get(j);
+    }
+  }
+}
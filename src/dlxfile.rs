@@ -0,0 +1,211 @@
+use std::vec::Vec;
+use std::io::File;
+use collections::hashmap::HashMap;
+use dlx::Matrix;
+use bitset::BitSet;
+use pentomino::Pentomino;
+use solve::{Placement, Columns};
+
+
+/// Lines of a DLX file that start with this are comments. In the
+/// line of column names, it also separates the primary columns from
+/// the secondary ones.
+static SEPARATOR: char = '|';
+
+/// Separates the least and most times a column can be covered,
+/// in a column name like `0:3|P1`
+static BOUNDS: char = ':';
+
+
+/// Names the columns of the placement matrix: `P<n>` for the
+/// `offset` pieces, and `<x>,<y>` for the squares of the board
+pub fn columnNames(board: &Pentomino, offset: uint) -> Vec<~str> {
+  let mut names = Vec::from_fn(offset, |i| format!("P{:u}", i));
+
+  for i in board.range() {
+    let (x, y) = board.getCoordinates(i);
+    names.push(format!("{:u},{:u}", x, y));
+  }
+
+  names
+}
+
+
+/// Writes the placement matrix as a DLX file, in the text format
+/// read by Knuth's exact cover solvers (and `loadDlx`).
+///
+/// The first line names the columns, with the primary columns before
+/// a `|` and the secondary columns after it. A column that can be
+/// covered more than once is written as a primary column with its
+/// bounds, `n|name` to be covered exactly `n` times, or `0:n|name`
+/// to be covered at most `n` times. Each line after that is a
+/// placement, naming the columns it has a one in.
+pub fn saveDlx(path: &Path, placements: &Vec<Placement>, cols: &Columns,
+               names: &Vec<~str>) {
+  if cols.groups().len() > 0 { fail!("groups can not be written to a DLX file!") }
+
+  let mut primary = Vec::new();
+  let mut secondary = Vec::new();
+
+  // Primary columns no placement covers are kept, so the
+  // matrix still has no solutions
+  for c in range(0, cols.len()) {
+    if !cols.active().get(c) && !cols.primary().get(c) { continue }
+
+    let name = names.get(c).as_slice();
+
+    match (cols.primary().get(c), cols.capacity(c)) {
+      (true, 1) => primary.push(name.to_owned()),
+      (false, 1) => secondary.push(name.to_owned()),
+      (true, n) => primary.push(format!("{:u}{}{:s}", n, SEPARATOR, name)),
+      (false, n) => primary.push(format!("0{}{:u}{}{:s}", BOUNDS, n, SEPARATOR, name))
+    }
+  }
+
+  let mut buf = format!("{} {:u} placements\n", SEPARATOR, placements.len());
+
+  buf.push_str(primary.connect(" "));
+  if secondary.len() > 0 { buf.push_str(format!(" {} {:s}", SEPARATOR, secondary.connect(" "))); }
+  buf.push_char('\n');
+
+  for p in placements.iter() {
+    let row: Vec<&str> = p.filled().iter().map(|c| names.get(*c).as_slice()).collect();
+    buf.push_str(row.connect(" "));
+    buf.push_char('\n');
+  }
+
+  match File::create(path).write_str(buf) {
+    Ok(_) => (),
+    Err(e) => fail!("DLX file error: {}", e)
+  }
+}
+
+
+/// An exact cover matrix read from a DLX file
+///
+///   * `names` - the name of each column
+///   * `primary` - columns that must be covered
+///   * `bounds` - the least and most times each column can be covered
+///   * `rows` - the columns each row has a one in
+pub struct DlxFile {
+  pub names: Vec<~str>,
+  pub primary: BitSet,
+  pub bounds: Vec<(uint, uint)>,
+  pub rows: Vec<Vec<uint>>
+}
+
+
+/// Parses a column name with optional bounds, `name`, `n|name` or
+/// `m:n|name`, returning the name and the bounds if any were given
+fn parseColumn(word: &str) -> (~str, Option<(uint, uint)>) {
+  let (bounds, name) = match word.rfind(SEPARATOR) {
+    Some(i) => (word.slice_to(i), word.slice_from(i + 1)),
+    None => return (word.to_owned(), None)
+  };
+
+  let number = |w: &str| match from_str(w) {
+    Some(n) => n,
+    None => fail!("invalid column `{:s}` in DLX file!", word)
+  };
+
+  let (min, max) = match bounds.find(BOUNDS) {
+    Some(i) => (number(bounds.slice_to(i)), number(bounds.slice_from(i + 1))),
+    None => (number(bounds), number(bounds))
+  };
+
+  if name.len() == 0 || min > max || max == 0 {
+    fail!("invalid column `{:s}` in DLX file!", word)
+  }
+
+  (name.to_owned(), Some((min, max)))
+}
+
+
+/// Reads an exact cover matrix from a DLX file (see `saveDlx`)
+pub fn loadDlx(path: &Path) -> DlxFile {
+  let contents = match File::open(path).read_to_str() {
+    Ok(s) => s,
+    Err(e) => fail!("DLX file error: {}", e)
+  };
+
+  let mut lines = contents.lines().filter(|l| {
+    let line = l.trim();
+    line.len() > 0 && !line.starts_with("|")
+  });
+
+  let header = match lines.next() {
+    Some(l) => l,
+    None => fail!("DLX file has no columns!")
+  };
+
+  let mut names = Vec::new();
+  let mut isPrimary = Vec::new();
+  let mut bounds = Vec::new();
+  let mut index = HashMap::new();
+  let mut secondary = false;
+
+  for word in header.words() {
+    if word == "|" { secondary = true; continue }
+
+    let (name, b) = parseColumn(word);
+
+    if b.is_some() && secondary { fail!("secondary column `{:s}` has bounds!", name) }
+    if index.contains_key(&name) { fail!("column `{:s}` is named twice!", name) }
+
+    index.insert(name.clone(), names.len());
+    names.push(name);
+    isPrimary.push(!secondary);
+    bounds.push(match b {
+      Some(b) => b,
+      None if secondary => (0, 1),
+      None => (1, 1)
+    });
+  }
+
+  let mut primary = BitSet::new(names.len());
+  for (c, p) in isPrimary.iter().enumerate() { primary.set(c, *p); }
+
+  let rows: Vec<Vec<uint>> = lines.map(|line| {
+    line.words().map(|w| match index.find(&w.to_owned()) {
+      Some(c) => *c,
+      None => fail!("unknown column `{:s}` in DLX file!", w)
+    }).collect()
+  }).collect();
+
+  DlxFile { names: names, primary: primary, bounds: bounds, rows: rows }
+}
+
+
+impl DlxFile {
+  /// Builds the matrix to search. A column with bounds other than
+  /// covering it exactly once is kept secondary, with its bounds
+  /// checked as a group, and is branched on once the other columns
+  /// are covered if it has not been covered enough times yet.
+  pub fn matrix(&self) -> Matrix {
+    let mut primary = self.primary.clone();
+
+    for (c, &(min, max)) in self.bounds.iter().enumerate() {
+      if min != 1 || max != 1 { primary.set(c, false); }
+    }
+
+    let mut matrix = Matrix::new(&primary);
+
+    for (c, &(min, max)) in self.bounds.iter().enumerate() {
+      if max > 1 { matrix.setCapacity(c, max); }
+      if self.primary.get(c) && (min != 1 || max != 1) {
+        matrix.addGroup(&Vec::from_elem(1, c), min, max);
+      }
+    }
+
+    for row in self.rows.iter() { matrix.addRow(row); }
+
+    matrix
+  }
+  /// The line of column names for row `r`
+  pub fn rowNames(&self, r: uint) -> ~str {
+    let names: Vec<&str> = self.rows.get(r).iter().map(|c| {
+      self.names.get(*c).as_slice()
+    }).collect();
+    names.connect(" ")
+  }
+}
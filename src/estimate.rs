@@ -0,0 +1,155 @@
+use std::vec::Vec;
+use std::io::stderr;
+use rand::Rng;
+use time::precise_time_ns;
+use dlx::Matrix;
+use prune::Prune;
+use heuristic::Heuristic;
+
+
+/// The estimated size of a search tree
+///
+///   * `nodes` - number of nodes the search will visit
+///   * `solutions` - number of solutions the search will find
+pub struct Estimate {
+  pub nodes: f64,
+  pub solutions: f64
+}
+
+
+/// Follows a single random path from the root of the search tree
+/// to a leaf, choosing columns the same way the search does. If
+/// the columns on the path had `d1, d2, ..` rows, the tree has
+/// about `1 + d1 + d1 * d2 + ..` nodes. Returns the estimate, and
+/// the rows chosen on the path.
+pub fn probe<R: Rng>(matrix: &mut Matrix, pruners: &mut Vec<~Prune:Send>,
+                     heuristic: &~Heuristic:Send, rng: &mut R) -> (Estimate, Vec<uint>) {
+  let mut estimate = Estimate { nodes: 1.0, solutions: 0.0 };
+  let mut weight = 1.0;
+  let mut path = Vec::new();
+  let mut hidden = Vec::new();
+
+  loop {
+    if matrix.blocked() { break }
+
+    let c = match heuristic.branch(matrix) {
+      Some(c) => c,
+      None => {
+        if matrix.satisfied() { estimate.solutions = weight; }
+        break
+      }
+    };
+
+    let len = matrix.len(c);
+
+    if len == 0 || pruners.mut_iter().any(|p| p.prune(&*matrix)) { break }
+
+    weight *= len as f64;
+    estimate.nodes += weight;
+
+    // Take a random row of the column
+    let mut n = matrix.down(c);
+    for _ in range(0, rng.gen_range(0, len)) { n = matrix.down(n); }
+
+    // The rows before it in a column with bounds are hidden,
+    // as the search does
+    path.push((n, hidden.len()));
+
+    if matrix.capacity(c) > 1 {
+      loop {
+        let m = matrix.down(c);
+        matrix.hide(m);
+        hidden.push(m);
+        if m == n { break }
+      }
+    }
+
+    matrix.select(n);
+  }
+
+  // Put the matrix back the way it was
+  for &(n, before) in path.iter().rev() {
+    matrix.unselect(n);
+
+    while hidden.len() > before { matrix.unhide(hidden.pop().unwrap()); }
+  }
+
+  (estimate, path.iter().map(|&(n, _)| matrix.row(n)).collect())
+}
+
+
+/// Estimates the size of the search tree of `matrix` by averaging
+/// `probes` random probes (Knuth's method), cutting the same branches
+/// as the `pruners` would, and choosing columns with the `heuristic`.
+/// The estimate is unbiased, but can vary a lot on uneven trees, so
+/// more probes give a more reliable answer.
+pub fn estimateTree<R: Rng>(matrix: &mut Matrix, probes: uint,
+                            pruners: &mut Vec<~Prune:Send>, heuristic: &~Heuristic:Send,
+                            rng: &mut R) -> Estimate {
+  let mut total = Estimate { nodes: 0.0, solutions: 0.0 };
+
+  for _ in range(0, probes) {
+    let (estimate, _) = probe(matrix, pruners, heuristic, rng);
+    total.nodes += estimate.nodes;
+    total.solutions += estimate.solutions;
+  }
+
+  if probes > 0 {
+    total.nodes /= probes as f64;
+    total.solutions /= probes as f64;
+  }
+
+  total
+}
+
+
+/// Formats a number of nanoseconds as hours, minutes and seconds
+fn formatDuration(ns: u64) -> ~str {
+  let seconds = ns / 1000000000;
+  format!("{}h{:02u}m{:02u}s", seconds / 3600, (seconds / 60 % 60) as uint,
+          (seconds % 60) as uint)
+}
+
+
+/// Prints the progress of a search to stderr every so often
+///
+///   * `interval` - nanoseconds between updates
+///   * `start` - when the search started
+///   * `last` - when the last update was printed
+pub struct Progress {
+  interval: u64,
+  start: u64,
+  last: u64
+}
+
+
+impl Progress {
+  pub fn new(seconds: uint) -> Progress {
+    let now = precise_time_ns();
+    Progress { interval: seconds as u64 * 1000000000, start: now, last: now }
+  }
+  /// Whether it is time to print another update
+  pub fn due(&self) -> bool {
+    precise_time_ns() - self.last >= self.interval
+  }
+  /// Prints the number of nodes visited and solutions found, how much
+  /// of the tree has been explored, and how much longer the search
+  /// should take at the current rate
+  pub fn report(&mut self, nodes: u64, found: uint, fraction: f64) {
+    let now = precise_time_ns();
+    let elapsed = now - self.start;
+    let eta = if fraction > 0.0 {
+      formatDuration((elapsed as f64 * (1.0 - fraction) / fraction) as u64)
+    } else {
+      ~"unknown"
+    };
+
+    stderr().write_str(format!("\rnodes: {}  solutions: {:u}  done: {:.2f}%  eta: {:s}   ",
+                               nodes, found, fraction * 100.0, eta));
+    self.last = now;
+  }
+  /// Ends the progress line
+  pub fn finish(&self) {
+    stderr().write_str("\n");
+  }
+}
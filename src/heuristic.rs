@@ -0,0 +1,172 @@
+use std::vec::Vec;
+use rand::{Rng, IsaacRng, SeedableRng};
+use dlx::Matrix;
+use pentomino::{Pentomino, Filled};
+
+
+/// Picks the column to branch on at each node of the search. Every
+/// heuristic picks a column with no rows left if there is one, since
+/// that makes the node a dead end.
+pub trait Heuristic {
+  /// The header of the uncovered primary column to branch on, or
+  /// None if every primary column has been covered
+  fn choose(&self, matrix: &Matrix) -> Option<uint>;
+  /// The header of the column to branch on: the column picked by
+  /// `choose`, or once every primary column has been covered, a
+  /// column with bounds that still has to be covered more times
+  fn branch(&self, matrix: &Matrix) -> Option<uint> {
+    match self.choose(matrix) {
+      None => matrix.unmet(),
+      c => c
+    }
+  }
+  /// Copies the heuristic, so each task of a search can have its own
+  fn boxClone(&self) -> ~Heuristic:Send;
+}
+
+
+/// Goes through the uncovered primary columns until `better` says
+/// a column should replace the one picked so far
+fn pick(matrix: &Matrix, better: |uint, uint| -> bool) -> Option<uint> {
+  let root = matrix.root();
+  let mut best = None;
+  let mut c = matrix.right(root);
+
+  while c != root {
+    if matrix.len(c) == 0 { return Some(c) }
+
+    best = match best {
+      Some(b) if !better(c, b) => Some(b),
+      _ => Some(c)
+    };
+
+    c = matrix.right(c);
+  }
+
+  best
+}
+
+
+/// Branches on the column with the fewest rows (minimum remaining
+/// values), breaking ties by the first column
+#[deriving(Clone)]
+pub struct MinRemaining;
+
+
+impl Heuristic for MinRemaining {
+  fn choose(&self, matrix: &Matrix) -> Option<uint> {
+    matrix.choose()
+  }
+  fn boxClone(&self) -> ~Heuristic:Send {
+    ~MinRemaining as ~Heuristic:Send
+  }
+}
+
+
+/// Branches on the first uncovered column
+#[deriving(Clone)]
+pub struct FirstColumn;
+
+
+impl Heuristic for FirstColumn {
+  fn choose(&self, matrix: &Matrix) -> Option<uint> {
+    pick(matrix, |_, _| false)
+  }
+  fn boxClone(&self) -> ~Heuristic:Send {
+    ~FirstColumn as ~Heuristic:Send
+  }
+}
+
+
+/// Branches on the uncovered column that comes first in a random
+/// order of the columns. The order is fixed by the seed, so a search
+/// visits the same tree each time it is run with the same seed, and
+/// can be resumed from a checkpoint.
+#[deriving(Clone)]
+pub struct RandomColumn {
+  rank: Vec<uint>
+}
+
+
+impl RandomColumn {
+  /// Shuffles the `headers` headers of a matrix with `seed`
+  pub fn new(headers: uint, seed: u32) -> RandomColumn {
+    let mut rng: IsaacRng = SeedableRng::from_seed(&[seed]);
+    let mut rank = Vec::from_fn(headers, |i| i);
+
+    rng.shuffle_mut(rank.as_mut_slice());
+
+    RandomColumn { rank: rank }
+  }
+}
+
+
+impl Heuristic for RandomColumn {
+  fn choose(&self, matrix: &Matrix) -> Option<uint> {
+    pick(matrix, |c, b| self.rank.get(c) < self.rank.get(b))
+  }
+  fn boxClone(&self) -> ~Heuristic:Send {
+    ~self.clone() as ~Heuristic:Send
+  }
+}
+
+
+/// Weight given to a column with no neighbours off the board
+static BASE_WEIGHT: uint = 8;
+
+
+/// Branches on the column with the fewest rows, counting the rows
+/// of squares near the corners and edges of the board as fewer. The
+/// weight of a square is `BASE_WEIGHT` plus the number of the eight
+/// squares around it that are off the board (or holes in it), and
+/// columns are compared by their rows divided by their weight. Pieces
+/// that fill the corners and edges early leave fewer awkward gaps.
+///
+/// Attributes
+///   * weight - the weight of the column of each header
+#[deriving(Clone)]
+pub struct EdgeWeighted {
+  weight: Vec<uint>
+}
+
+
+impl EdgeWeighted {
+  /// Weighs the squares of `board`, whose columns come after
+  /// the columns of the `offset` pieces
+  pub fn new(board: &Pentomino, offset: uint) -> EdgeWeighted {
+    let onBoard = |x: int, y: int| {
+      x >= 0 && y >= 0 && match board.get_opt(x as uint, y as uint) {
+        Some(&Filled(_)) => true,
+        _ => false
+      }
+    };
+    let mut weight = Vec::from_elem(offset + board.area() + 1, BASE_WEIGHT);
+
+    for i in board.range() {
+      let (x, y) = board.getCoordinates(i);
+
+      for dy in range(-1, 2) {
+        for dx in range(-1, 2) {
+          // The header of a column comes one after it
+          if !onBoard(x as int + dx, y as int + dy) {
+            *weight.get_mut(offset + i + 1) += 1;
+          }
+        }
+      }
+    }
+
+    EdgeWeighted { weight: weight }
+  }
+}
+
+
+impl Heuristic for EdgeWeighted {
+  fn choose(&self, matrix: &Matrix) -> Option<uint> {
+    pick(matrix, |c, b| {
+      matrix.len(c) * *self.weight.get(b) < matrix.len(b) * *self.weight.get(c)
+    })
+  }
+  fn boxClone(&self) -> ~Heuristic:Send {
+    ~self.clone() as ~Heuristic:Send
+  }
+}
@@ -4,31 +4,70 @@
 
 #[phase(syntax, link)] extern crate log;
 extern crate rand;
+extern crate sync;
+extern crate time;
 extern crate collections;
 
 
 use std::os;
 use std::vec::Vec;
-use std::io::{File, Truncate, Write};
-use parse::parseFile;
+use std::io::{File, Open, Truncate, Write, SeekEnd, stderr};
+use std::sync::atomics::{AtomicUint, SeqCst};
+use sync::Arc;
+use collections::hashmap::HashSet;
+use rand::task_rng;
+use rand::{IsaacRng, SeedableRng};
+use time::precise_time_ns;
+use parse::{Marks, parseFile};
 use pentomino::Pentomino;
-use solve::{generatePlacements, solve};
-use solution::{convertSolutions, removeIsometric};
+use bitset::BitSet;
+use solve::{Placement, Columns, Limits, Status, Searching, Complete, TimedOut, 
+            Cancelled, Solutions, Pin, generatePlacements, restrictPlacements, 
+            shufflePlacements, buildMatrix};
+use symmetry::Symmetry;
+use parallel::solveParallel;
+use checkpoint::{Checkpoint, Checkpointer, puzzleHash};
+use estimate::{Progress, estimateTree};
+use stats::Stats;
+use prune::{Prune, RegionPrune, ColourPrune, Colouring};
+use heuristic::{Heuristic, MinRemaining, FirstColumn, RandomColumn, EdgeWeighted};
+use sample::sampleSolutions;
+use diagnose::diagnose;
+use colour::Palette;
+use dlxfile::{columnNames, saveDlx, loadDlx};
+use cnf::{Pairwise, Sequential, encodePlacements, loadModel};
+use solution::{generateSymbols, convertSolution, convertSolutions, unusedPieces};
 use cmd::{OptionParser, BoolOption, StrOption,
           ToggleOption, UintOption}; 
 
 
 mod cmd;
+mod dlx;
+mod cancel;
+mod bitset;
 mod solve;
 mod parse;
+mod parallel;
 mod solution;
+mod symmetry;
 mod pentomino;
+mod checkpoint;
+mod estimate;
+mod stats;
+mod prune;
+mod heuristic;
+mod sample;
+mod diagnose;
+mod dlxfile;
+mod cnf;
+mod colour;
+mod region;
 
 
 /// Finds the Board in a vector of pentominoes, and removes it
-/// and returns it.
+/// (and its marks) and returns it.
 #[inline]
-fn discoverBoard(pentominoes: &mut Vec<Pentomino>) -> Pentomino {
+fn discoverBoard(pentominoes: &mut Vec<Pentomino>, marks: &mut Vec<Marks>) -> Pentomino {
   let mut index = 0;
   let mut max = 0;
   
@@ -39,6 +78,8 @@ fn discoverBoard(pentominoes: &mut Vec<Pentomino>) -> Pentomino {
     }
   }
 
+  marks.remove(index);
+
   match pentominoes.remove(index) {
     Some(piece) => piece,
     None => fail!("no board to remove")
@@ -46,18 +87,300 @@ fn discoverBoard(pentominoes: &mut Vec<Pentomino>) -> Pentomino {
 }
 
 
+/// The limits on the search given by the options
+fn newLimits(parser: &OptionParser) -> Limits {
+  let timeout = parser.getUintOption("timeout");
+
+  Limits {
+    maxSolutions: parser.getUintOption("solutions"),
+    cancel: None,
+    deadline: if timeout > 0 { 
+      Some(precise_time_ns() + timeout as u64 * 1000000000) 
+    } else { 
+      None 
+    }
+  }
+}
+
+
+/// Solves an exact cover matrix read from a DLX file, printing each
+/// solution as the lines of the rows it uses
+fn solveDlx(path: &Path, parser: &OptionParser) {
+  let file = loadDlx(path);
+  let matrix = file.matrix();
+  let limits = newLimits(parser);
+  let threads = parser.getUintOption("threads");
+  let count = parser.getBoolOption("count");
+  let heuristic = ~MinRemaining as ~Heuristic:Send;
+
+  let mut outputFile = if parser.getStrOption("output") != &~"" {
+    match File::create(&Path::new(parser.getStrOption("output").to_owned())) {
+      Ok(f) => Some(f),
+      Err(e) => fail!("output file error: {}", e)
+    }
+  } else {
+    None
+  };
+
+  let (found, status) = {
+    let write = |solution: &Vec<uint>| -> bool {
+      if count { return true }
+
+      let mut buf = ~"";
+
+      for r in solution.iter() {
+        buf.push_str(file.rowNames(*r));
+        buf.push_char('\n');
+      }
+
+      match outputFile {
+        Some(ref mut f) => { f.write_str(buf); f.write_str("\n"); }
+        None => println!("{:s}", buf)
+      }
+
+      true
+    };
+
+    if threads > 1 {
+      solveParallel(matrix, threads, limits, parser.getBoolOption("deterministic"),
+                    &Vec::new(), &heuristic, &write)
+    } else {
+      Solutions::new(matrix, Vec::new(), limits).run(&write)
+    }
+  };
+
+  if count { println!("solutions: {:u}", found); }
+  reportStatus(status, found);
+}
+
+
+/// Runs the search on a single task, or splits it 
+/// between `threads` tasks. Checkpoints, progress and 
+/// statistics are only supported on a single task. If `split`
+/// is set, separate regions of the board are solved one at a time.
+fn runSearch(placements: &Vec<Placement>, cols: &Columns,
+             limits: Limits, threads: uint, deterministic: bool, split: bool,
+             checkpointer: Option<Checkpointer>, resume: &Option<Checkpoint>,
+             progress: Option<Progress>, stats: &mut Option<Stats>,
+             pruners: &Vec<~Prune:Send>, heuristic: &~Heuristic:Send,
+             success: &|&Vec<uint>| -> bool) -> (uint, Status) {
+  let matrix = buildMatrix(placements, cols);
+
+  if threads > 1 {
+    return solveParallel(matrix, threads, limits, deterministic, split, pruners, 
+                         heuristic, success)
+  }
+
+  let mut solutions = Solutions::new(matrix, Vec::new(), limits);
+
+  for p in pruners.iter() { solutions.pruneWith(p.boxClone()); }
+  solutions.chooseWith(heuristic.boxClone());
+  if split { solutions.splitRegions(); }
+
+  match checkpointer {
+    Some(c) => solutions.checkpointTo(c),
+    None => ()
+  }
+
+  match *resume {
+    Some(ref c) => solutions.resume(c),
+    None => ()
+  }
+
+  match progress {
+    Some(p) => solutions.reportTo(p),
+    None => ()
+  }
+
+  match stats.take() {
+    Some(s) => solutions.collectStats(s),
+    None => ()
+  }
+
+  let result = solutions.run(success);
+  *stats = solutions.takeStats();
+  result
+}
+
+
+/// Counts the solutions on a single task, solving separate
+/// regions of the board one at a time and multiplying their
+/// counts, without going through the solutions one by one
+fn countRegions(placements: &Vec<Placement>, cols: &Columns, limits: Limits,
+                progress: Option<Progress>, stats: &mut Option<Stats>,
+                pruners: &Vec<~Prune:Send>, heuristic: &~Heuristic:Send) -> (uint, Status) {
+  let matrix = buildMatrix(placements, cols);
+  let mut solutions = Solutions::new(matrix, Vec::new(), limits);
+
+  for p in pruners.iter() { solutions.pruneWith(p.boxClone()); }
+  solutions.chooseWith(heuristic.boxClone());
+  solutions.splitRegions();
+
+  match progress {
+    Some(p) => solutions.reportTo(p),
+    None => ()
+  }
+
+  match stats.take() {
+    Some(s) => solutions.collectStats(s),
+    None => ()
+  }
+
+  let result = solutions.count();
+  *stats = solutions.takeStats();
+  result
+}
+
+
+/// Collects statistics about the search if a
+/// file to write them to was given
+fn newStats(parser: &OptionParser, placements: &Vec<Placement>,
+            offset: uint) -> Option<Stats> {
+  if parser.getStrOption("stats") == &~"" { return None }
+
+  let pieceOf = placements.iter().map(|p| *p.filled().get(0)).collect();
+  Some(Stats::new(pieceOf, offset))
+}
+
+
+/// Writes the statistics collected about the search
+fn saveStats(parser: &OptionParser, stats: &Option<Stats>,
+             found: uint, status: Status) {
+  match *stats {
+    Some(ref s) => {
+      let path = Path::new(parser.getStrOption("stats").to_owned());
+      s.save(&path, found, format!("{}", status).as_slice());
+    }
+    None => ()
+  }
+}
+
+
+/// Prints an estimate of the size of the search tree
+/// made from `probes` random probes
+fn printEstimate(placements: &Vec<Placement>, cols: &Columns,
+                 probes: uint, pruners: &Vec<~Prune:Send>,
+                 heuristic: &~Heuristic:Send) {
+  let mut matrix = buildMatrix(placements, cols);
+  let mut pruners = pruners.iter().map(|p| p.boxClone()).collect();
+  let estimate = estimateTree(&mut matrix, probes, &mut pruners, heuristic, 
+                              &mut task_rng());
+
+  println!("estimated nodes: {:.0f}", estimate.nodes);
+  println!("estimated solutions: {:.0f}", estimate.solutions);
+}
+
+
+/// Makes the pruners named in a comma separated list
+fn newPruners(names: &str, board: &Pentomino, pentominoes: &Vec<Pentomino>,
+              cols: &Columns) -> Vec<~Prune:Send> {
+  let mut pruners = Vec::new();
+  let colourings = |colourings: Vec<Colouring>, pruners: &mut Vec<~Prune:Send>| {
+    for colouring in colourings.move_iter() {
+      pruners.push(~ColourPrune::new(board, pentominoes, cols.primary(),
+                                     colouring) as ~Prune:Send);
+    }
+  };
+
+  for name in names.split(',').filter(|n| n.len() > 0) {
+    match name {
+      "regions" => pruners.push(~RegionPrune::new(board, pentominoes) as ~Prune:Send),
+      "parity" => colourings(Vec::from_elem(1, Colouring::checkerboard()), &mut pruners),
+      _ if name.starts_with("colours") => match from_str(name.slice_from(7)) {
+        Some(k) if k > 1 => colourings(Colouring::stripes(k), &mut pruners),
+        _ => fail!("invalid number of colours in `{:s}`!", name)
+      },
+      _ => fail!("unknown pruner `{:s}`!", name)
+    }
+  }
+
+  pruners
+}
+
+
+/// Makes the heuristic that picks the column to branch on
+fn newHeuristic(name: &str, board: &Pentomino, offset: uint,
+                cols: &Columns) -> ~Heuristic:Send {
+  match name {
+    "mrv" => ~MinRemaining as ~Heuristic:Send,
+    "first" => ~FirstColumn as ~Heuristic:Send,
+    "edges" => ~EdgeWeighted::new(board, offset) as ~Heuristic:Send,
+    "random" => ~RandomColumn::new(cols.len() + 1, 0) as ~Heuristic:Send,
+    _ if name.starts_with("random:") => match from_str(name.slice_from(7)) {
+      Some(seed) => ~RandomColumn::new(cols.len() + 1, seed) as ~Heuristic:Send,
+      None => fail!("invalid seed in `{:s}`!", name)
+    },
+    _ => fail!("unknown heuristic `{:s}`!", name)
+  }
+}
+
+
+/// Checks the puzzle with the pruners before searching, so puzzles
+/// they show can not be solved are reported at once
+fn precheck(placements: &Vec<Placement>, cols: &Columns, pruners: &Vec<~Prune:Send>) -> bool {
+  let matrix = buildMatrix(placements, cols);
+  pruners.iter().all(|p| !p.boxClone().prune(&matrix))
+}
+
+
+/// Tells the user when the search did not explore the
+/// whole tree, and so may have missed solutions
+fn reportStatus(status: Status, found: uint) {
+  let reason = match status {
+    TimedOut => "ran out of time",
+    Cancelled => "was cancelled",
+    _ => return
+  };
+
+  stderr().write_line(format!("search incomplete: the search {:s} after finding {:u} solutions",
+                              reason, found));
+}
+
+
 /// Help Text
 static USAGE_TEXT: &'static str = "
   usage: ./pentominoes <filename> [--reflections=true|false]         
          [--rotations=true|false] [--help] [--solutions=0|..|n]       
          [--output=filename.txt] [--all-solutions]
+         [--threads=1|..|n] [--deterministic] [--split] [--count]
+         [--timeout=0|..|n] [--checkpoint=filename]
+         [--checkpoint-interval=1|..|n] [--resume=filename]
+         [--estimate=0|..|n] [--progress=0|..|n]
+         [--stats=filename.json] [--prune=regions,parity,colours<k>]
+         [--heuristic=mrv|first|edges|random:<seed>]
+         [--random] [--seed=0|..|n] [--sample=0|..|n] [--diagnose]
+         [--export-dlx=filename.dlx] [--dlx]
+         [--export-cnf=filename.cnf] [--cnf-encoding=pairwise|sequential]
+         [--import-model=filename]
                                                               
   options:                                                    
     all-solutions   toggle showing all solutions (including isometric ones)
+    checkpoint      save the position of the search to a file, to continue it later with resume
+    checkpoint-interval  seconds between saving checkpoints (one is also saved when the search stops)
+    count           only print the number of solutions, and the number that are not isometric
+    diagnose        list reasons the puzzle can not be solved (squares and pieces that fit nowhere, too few squares, colour mismatches), and exit
+    dlx             read the file as an exact cover matrix in the DLX format, and print the rows of each solution
+    export-cnf      write the placements to a file as a SAT instance in the DIMACS format, and exit
+    cnf-encoding    how export-cnf limits the placements of each square and piece: pairwise or sequential (counter)
+    import-model    print the solution in a model a SAT solver found for export-cnf (given the same options), and exit
+    export-dlx      write the placement matrix to a file in the DLX format used by other exact cover solvers, and exit
+    deterministic   with threads, find solutions in the same order as a single thread
+    estimate        estimate the size of the search tree from this many random probes, and exit
     reflections     include reflections in the solution space 
+    resume          continue the search from a checkpoint file (the output file is cut back to where it was at the checkpoint, and solutions are added after it; printed solutions found since the checkpoint are printed again)
     rotations       include rotations in the solution space   
     output          write the solutions to an output file, otherwise print to stdout
+    prune           comma separated checks that cut dead branches early: regions (empty areas the pieces left can not fill), parity (checkerboard colours), colours<k> (stripes of k colours)
+    progress        print the progress of the search to stderr every this many seconds (set to 0 for none)
     solutions       number of solutions to look for (set to 0 to look for all possible)          
+    split           solve the regions the board splits into (walls, or the pieces placed so far, cut it into parts no piece can reach across) one at a time, so solutions come out in a different order than without split. with count, the counts of the regions are multiplied, and distinct solutions are not counted. not with sample or deterministic threads, and with count, not with threads, checkpoints or solutions
+    stats           write statistics about the search to a JSON file
+    threads         number of tasks to split the search between
+    timeout         stop searching after this many seconds, keeping the solutions found (set to 0 for no limit)
+    random          try the placements in a random order fixed by the seed, to find varied solutions
+    sample          find this many different solutions at random, close to uniformly, by random restarts
+    seed            seed for random and sample (the same seed gives the same solutions)
+    heuristic       how to pick the square or piece to branch on: mrv (fewest placements), first, edges (fewest placements, favouring squares near the edges), random:<seed> (an order fixed by the seed). compare them with --stats
     help            print help and exit 
 ";
 
@@ -72,6 +395,28 @@ fn main() {
   parser.addOption("help", ToggleOption(false));
   parser.addOption("solutions", UintOption(0));
   parser.addOption("all-solutions", ToggleOption(false));
+  parser.addOption("threads", UintOption(1));
+  parser.addOption("deterministic", ToggleOption(false));
+  parser.addOption("split", ToggleOption(false));
+  parser.addOption("count", ToggleOption(false));
+  parser.addOption("timeout", UintOption(0));
+  parser.addOption("checkpoint", StrOption(~""));
+  parser.addOption("checkpoint-interval", UintOption(60));
+  parser.addOption("resume", StrOption(~""));
+  parser.addOption("estimate", UintOption(0));
+  parser.addOption("progress", UintOption(0));
+  parser.addOption("stats", StrOption(~""));
+  parser.addOption("prune", StrOption(~""));
+  parser.addOption("heuristic", StrOption(~"mrv"));
+  parser.addOption("random", ToggleOption(false));
+  parser.addOption("seed", UintOption(0));
+  parser.addOption("sample", UintOption(0));
+  parser.addOption("diagnose", ToggleOption(false));
+  parser.addOption("dlx", ToggleOption(false));
+  parser.addOption("export-dlx", StrOption(~""));
+  parser.addOption("export-cnf", StrOption(~""));
+  parser.addOption("cnf-encoding", StrOption(~"pairwise"));
+  parser.addOption("import-model", StrOption(~""));
 
   // Not enough arguments supplied
   if !(args.len() > 1) { println!("{:s}", USAGE_TEXT); return }
@@ -83,14 +428,106 @@ fn main() {
 
   // Parse the file
   let path = Path::new(args[1]);
-  let mut pentominoes = parseFile(&path);
+
+  // The file is a matrix to solve, rather than a puzzle
+  if parser.getBoolOption("dlx") { solveDlx(&path, &parser); return }
+
+  let (mut pentominoes, mut marks, rules) = parseFile(&path);
+
+  // A checkpoint is only valid for the same puzzle, searched
+  // with the same options that shape the search tree
+  let hash = match File::open(&path).read_to_end() {
+    Ok(contents) => {
+      let options = format!("rotations={} reflections={} all-solutions={} count={} heuristic={} random={} seed={}",
+                            parser.getBoolOption("rotations"),
+                            parser.getBoolOption("reflections"),
+                            parser.getBoolOption("all-solutions"),
+                            parser.getBoolOption("count"),
+                            parser.getStrOption("heuristic"),
+                            parser.getBoolOption("random"),
+                            parser.getUintOption("seed"));
+      puzzleHash(contents.as_slice(), options.as_slice())
+    }
+    Err(e) => fail!("file error: {}", e)
+  };
+
+  let board = discoverBoard(&mut pentominoes, &mut marks);
+  let palette = Palette::new(&rules);
+
+  // Fix pinned pieces in place. Only one copy of a piece is
+  // pinned, so the other copies are split off from it.
+  let mut pins: Vec<Pin> = Vec::new();
+
+  for rule in rules.pins.iter() {
+    let found = range(0, pentominoes.len()).find(|i| {
+      marks.get(*i).labels.contains(&rule.mark) && !pins.iter().any(|p| p.piece == *i)
+    });
+
+    let mut piece = match found {
+      Some(i) => i,
+      None => fail!("no piece left to pin with `{}`!", rule.mark)
+    };
+
+    if marks.get(piece).copies > 1 {
+      let mut split = marks.get(piece).clone();
+      split.copies = 1;
+      marks.get_mut(piece).copies -= 1;
+
+      let shape = pentominoes.get(piece).clone();
+      pentominoes.push(shape);
+      marks.push(split);
+      piece = pentominoes.len() - 1;
+    }
+
+    pins.push(Pin { piece: piece, turns: rule.turns, reflected: rule.reflected, 
+                    x: rule.x, y: rule.y });
+  }
 
   if pentominoes.len() > 26 { fail!("too many pieces, can't map pieces to alphabet!") }
 
-  let board = discoverBoard(&mut pentominoes);
+  // Squares each piece may not cover
+  let mut forbidden = Vec::from_fn(pentominoes.len(), |_| BitSet::new(board.area()));
+
+  for rule in rules.forbids.iter() {
+    if rule.x1 >= board.dimX || rule.y1 >= board.dimY { 
+      fail!("forbidden squares for `{}` are off the board!", rule.mark) 
+    }
+
+    for i in range(0, pentominoes.len()) {
+      if !marks.get(i).labels.contains(&rule.mark) { continue }
+
+      for y in range(rule.y0, rule.y1 + 1) {
+        for x in range(rule.x0, rule.x1 + 1) {
+          forbidden.get_mut(i).set(board.getIndex(x, y), true);
+        }
+      }
+    }
+  }
+
+  let copies: Vec<uint> = marks.iter().map(|m| m.copies).collect();
+
+  // Explain why the puzzle can not be solved, instead of solving it
+  if parser.getBoolOption("diagnose") {
+    let useRotations = parser.getBoolOption("rotations");
+    let useReflections = parser.getBoolOption("reflections");
+    let (cols, placements) = generatePlacements(&board, &pentominoes, &marks, &palette,
+                                                &pins, &forbidden, useRotations, 
+                                                useReflections);
+    let reasons = diagnose(&board, &pentominoes, &marks, &palette, &pins, &forbidden, 
+                           &cols, &placements, useRotations, useReflections);
+
+    if reasons.len() == 0 {
+      println!("no simple reason found that the puzzle can not be solved");
+    }
+
+    for reason in reasons.iter() { println!("{:s}", *reason); }
+    return
+  }
 
   // Validate
-  let totalPieceSize = pentominoes.iter().fold(0, |a, b| a + b.size());
+  let totalPieceSize = pentominoes.iter().zip(marks.iter()).fold(0, |a, (b, m)| {
+    a + b.size() * m.copies
+  });
 
   if totalPieceSize < board.size() {
     fail!("board has {:u} squares, only {:u} squares in pieces!", board.size(), totalPieceSize);
@@ -99,51 +536,334 @@ fn main() {
              board.size(), totalPieceSize);
   }
 
+  let mandatorySize = pentominoes.iter().zip(marks.iter()).fold(0, |a, (b, m)| {
+    if m.mandatory { a + b.size() * m.copies } else { a }
+  });
+
+  if mandatorySize > board.size() {
+    fail!("board has {:u} squares, but the mandatory pieces have {:u} squares!", 
+          board.size(), mandatorySize);
+  }
+
   // Begin Solving
   let offset = pentominoes.len();
-  let mut solutions = Vec::new();
-  let mut solutionsNum: uint = 0;
-  let (mut cols, mut placements) = generatePlacements(&board, &pentominoes, 
+  let (mut cols, mut placements) = generatePlacements(&board, &pentominoes, &marks, 
+                                                      &palette, &pins, &forbidden,
                                                       parser.getBoolOption("rotations"), 
                                                       parser.getBoolOption("reflections"));
   let rows = placements.len();
+  let seed = parser.getUintOption("seed") as u32;
+
+  if parser.getBoolOption("random") { shufflePlacements(&mut placements, seed); }
+
+  // Limit how many pieces of each group are used
+  for g in rules.groups.iter() {
+    let members: Vec<uint> = range(0, offset).filter(|i| {
+      marks.get(*i).labels.contains(&g.mark)
+    }).collect();
+
+    if members.len() == 0 { fail!("no pieces are marked with `{}` for a group!", g.mark) }
+
+    cols.addGroup(members, g.min, g.max);
+  }
+
+  if parser.getStrOption("export-dlx") != &~"" {
+    saveDlx(&Path::new(parser.getStrOption("export-dlx").to_owned()), &placements, &cols,
+            &columnNames(&board, offset));
+    return
+  }
+
+  if parser.getStrOption("export-cnf") != &~"" {
+    let encoding = match parser.getStrOption("cnf-encoding").as_slice() {
+      "pairwise" => Pairwise,
+      "sequential" => Sequential,
+      e => fail!("unknown encoding `{:s}`!", e)
+    };
+    let comment = format!("{:u} placements of {:s}", placements.len(), args[1]);
+
+    encodePlacements(&placements, &cols, encoding).save(
+      &Path::new(parser.getStrOption("export-cnf").to_owned()), comment);
+    return
+  }
+
+  // Show the solution a SAT solver found for the exported instance.
+  // The placements are made the same way, so the options must match.
+  if parser.getStrOption("import-model") != &~"" {
+    let solution = loadModel(&Path::new(parser.getStrOption("import-model").to_owned()),
+                             placements.len());
+
+    for c in cols.primary().iter().filter(|c| *c >= offset) {
+      let covered = solution.iter().filter(|r| placements.get(**r).filled().contains(&c)).count();
+      if covered != 1 { fail!("model does not match the puzzle!") }
+    }
+
+    let boards = convertSolutions(&board, &Vec::from_elem(1, solution), &placements, offset);
+
+    for b in boards.iter() { println!("{:s}\n", b.to_str()); }
+    return
+  }
 
   debug!("{:u}x{:u} Board", board.dimX, board.dimY);
   debug!("Pieces: {:u}", offset);
-  debug!("Columns: {:u}", cols.len());
+  debug!("Columns: {:u} ({:u} active)", cols.len(), cols.active().count());
   debug!("Rows: {:u}", rows); 
 
-  solve(&mut placements, &mut cols, &mut Vec::from_elem(rows, true),  
-        &mut solutionsNum, 0, &mut Vec::with_capacity(offset),
-        parser.getUintOption("solutions"), offset,
-        &|solution| { solutions.push(solution.clone()); });
+  let threads = parser.getUintOption("threads");
+  let deterministic = parser.getBoolOption("deterministic");
+  let split = parser.getBoolOption("split");
+  let limits = newLimits(&parser);
 
-  debug!("Solutions Found: {:u}", solutionsNum);
+  let resume = if parser.getStrOption("resume") != &~"" {
+    let checkpoint = Checkpoint::load(&Path::new(parser.getStrOption("resume").to_owned()));
+    if checkpoint.hash != hash { fail!("checkpoint was saved for a different puzzle or options!") }
+    Some(checkpoint)
+  } else {
+    None
+  };
+
+  if threads > 1 && (resume.is_some() || parser.getStrOption("checkpoint") != &~"") {
+    fail!("checkpoints can only be used with a single thread!")
+  }
+
+  let probes = parser.getUintOption("estimate");
+  let pruners = newPruners(parser.getStrOption("prune").as_slice(), &board,
+                           &pentominoes, &cols);
 
-  // Convert solution vectors to Pentominos
-  let mut boards = convertSolutions(&board, &solutions, &placements, offset);
+  if !precheck(&placements, &cols, &pruners) {
+    stderr().write_line("no solutions (the pieces can not cover the board)");
+    return
+  }
+
+  let heuristic = newHeuristic(parser.getStrOption("heuristic").as_slice(), &board, 
+                               offset, &cols);
+  let progress = match parser.getUintOption("progress") {
+    0 => None,
+    _ if threads > 1 => fail!("progress can only be shown with a single thread!"),
+    seconds => Some(Progress::new(seconds))
+  };
+
+  if threads > 1 && parser.getStrOption("stats") != &~"" {
+    fail!("statistics can only be collected with a single thread!")
+  }
+
+  let samples = parser.getUintOption("sample");
+
+  if samples > 0 && (threads > 1 || parser.getBoolOption("count") || resume.is_some() ||
+                     parser.getStrOption("checkpoint") != &~"" || progress.is_some() ||
+                     parser.getStrOption("stats") != &~"") {
+    fail!("sampling can not be used with threads, count, checkpoints, progress or stats!")
+  }
+
+  // Sampling walks down the whole tree at random, and tasks choose
+  // the regions of their own subtrees, not in the order of a single
+  // task. Counting multiplies the counts of the regions, so there is
+  // no single position in the tree, or number of solutions, to stop at.
+  if split && samples > 0 {
+    fail!("split can not be used with sample!")
+  }
 
-  // Remove all isometric solutions
-  if !parser.getBoolOption("all-solutions") {
-    debug!("Removing isometric solutions...");
-    removeIsometric(&mut boards);
-    debug!("Non-Isometric Solutions: {:u}", boards.len());
+  if split && threads > 1 && deterministic {
+    fail!("split can not be used with deterministic threads!")
+  }
+
+  if split && parser.getBoolOption("count") &&
+     (threads > 1 || resume.is_some() || parser.getStrOption("checkpoint") != &~"" ||
+      parser.getUintOption("solutions") > 0) {
+    fail!("split can not count with threads, checkpoints or solutions!")
+  }
+
+  // The number of solutions written (or the number of distinct
+  // solutions, when counting) is saved with each checkpoint
+  let tally = Arc::new(AtomicUint::new(match resume {
+    Some(ref c) => c.tally,
+    None => 0
+  }));
+
+  // As is the length of the output file, so solutions written after
+  // the checkpoint can be cut off when they are found again
+  let outputLen = Arc::new(AtomicUint::new(match resume {
+    Some(ref c) => c.output,
+    None => 0
+  }));
+
+  let checkpointer = if parser.getStrOption("checkpoint") != &~"" {
+    Some(Checkpointer::new(Path::new(parser.getStrOption("checkpoint").to_owned()),
+                           parser.getUintOption("checkpoint-interval"), hash,
+                           tally.clone(), outputLen.clone()))
+  } else {
+    None
+  };
+
+  // Only count the solutions, and the solutions that come first
+  // among their rotations and reflections, without keeping any
+  if parser.getBoolOption("count") {
+    if probes > 0 { printEstimate(&placements, &cols, probes, &pruners, &heuristic); return }
+
+    let mut stats = newStats(&parser, &placements, offset);
+
+    // The solutions are never gone through one by one, so
+    // the distinct ones can not be counted
+    if split {
+      let (found, status) = countRegions(&placements, &cols, limits.clone(), progress,
+                                         &mut stats, &pruners, &heuristic);
+
+      saveStats(&parser, &stats, found, status);
+
+      println!("solutions: {:u}", found);
+      reportStatus(status, found);
+      return
+    }
+
+    // Pins and forbidden squares can leave the placements with
+    // fewer symmetries than the board
+    let mut symmetry = Symmetry::new(&board, &palette);
+    symmetry.restrictTo(&placements, offset);
+
+    let found = {
+      let count = |solution: &Vec<uint>| -> bool {
+        if symmetry.isCanonical(solution, &placements, offset) { tally.fetch_add(1, SeqCst); }
+        true
+      };
+
+      runSearch(&placements, &cols, limits.clone(), threads, deterministic, split,
+                checkpointer, &resume, progress, &mut stats, &pruners, &heuristic, &count)
+    };
+
+    let (found, status) = found;
+
+    saveStats(&parser, &stats, found, status);
+
+    println!("solutions: {:u}", found);
+    println!("distinct: {:u}", tally.load(SeqCst));
+    reportStatus(status, found);
+    return
+  }
+
+  let allSolutions = parser.getBoolOption("all-solutions");
+  let mut checkIsometric = !allSolutions;
+
+  // Only search for one solution out of each class of isometric 
+  // solutions, by restricting the placements of one piece. The
+  // piece has to be in every solution exactly once, so it must 
+  // have one copy, and be mandatory or every piece must be used.
+  let candidates: Vec<bool> = marks.iter().map(|m| {
+    m.copies == 1 && (m.mandatory || totalPieceSize == board.size())
+  }).collect();
+
+  if !allSolutions && candidates.iter().any(|c| *c) {
+    let mut symmetry = Symmetry::new(&board, &palette);
+    let complete = symmetry.restrictTo(&placements, offset);
+
+    match symmetry.breakSymmetry(&placements, offset, &candidates) {
+      Some((piece, keep)) => {
+        debug!("Breaking {:u} symmetries with piece {:u}", symmetry.order(), piece);
+        placements = restrictPlacements(placements, &mut cols, &keep);
+
+        // A symmetry that was dropped could still map
+        // some solutions onto each other
+        checkIsometric = !complete;
+      }
+      None => debug!("No piece can break the symmetries of the board")
+    }
+  }
+
+  if probes > 0 { printEstimate(&placements, &cols, probes, &pruners, &heuristic); return }
+
+  // Solutions found before the checkpoint are not kept, so
+  // isometric copies of them can not be recognized
+  if resume.is_some() && checkIsometric {
+    stderr().write_line("warning: solutions isometric to ones found before the checkpoint may be repeated");
   }
 
   // Write the output to a File
-  // or print to stdout
-  if parser.getStrOption("output") != &~"" {
+  // or print to stdout, adding on to the
+  // solutions written before a checkpoint
+  let mut outputFile = if parser.getStrOption("output") != &~"" {
     let outputPath = Path::new(parser.getStrOption("output").to_owned());
-    let mut outputFile = match File::open_mode(&outputPath, Truncate, Write) {
-      Ok(f) => f,
+    let mode = if resume.is_some() { Open } else { Truncate };
+    let opened = File::open_mode(&outputPath, mode, Write).and_then(|mut f| {
+      let len = outputLen.load(SeqCst) as i64;
+      f.truncate(len).and_then(|_| f.seek(0, SeekEnd)).map(|_| f)
+    });
+
+    match opened {
+      Ok(f) => Some(f),
       Err(e) => { fail!("output file error: {}", e) }
+    }
+  } else {
+    None
+  };
+
+  // Isometric solutions are told apart by the placements they use,
+  // under every rotation and reflection of the board
+  let symbols = generateSymbols(offset);
+  let isometries = Symmetry::new(&board, &palette);
+  let mut unique = HashSet::new();
+  let mut stats = newStats(&parser, &placements, offset);
+
+  let (solutionsNum, status) = {
+    // Convert each solution vector to a Pentomino and write it out
+    // as soon as it is found, along with the pieces it left out, 
+    // skipping isometric solutions that were not already ruled 
+    // out by breaking symmetry. The search goes on either way, but a
+    // skipped solution does not count as one of the samples
+    let write = |solution: &Vec<uint>| -> bool {
+      let fresh = !checkIsometric ||
+                  unique.insert(isometries.canonical(solution, &placements, offset));
+
+      if fresh {
+        let b = convertSolution(&board, solution, &placements, offset, &symbols);
+        let written = tally.fetch_add(1, SeqCst) + 1;
+        let mut unused = ~"";
+
+        for i in unusedPieces(solution, &placements, &copies).iter() {
+          unused.push_str(format!("{:s}\n\n", pentominoes.get(*i).to_str()));
+        }
+
+        if unused.len() > 0 { unused = format!("-- Unused pieces --\n{:s}", unused); }
+
+        match outputFile {
+          Some(ref mut f) => {
+            let text = format!("-- Solution {:u} --\n{:s}\n\n{:s}", written, b.to_str(), unused);
+            f.write_str(text);
+            outputLen.fetch_add(text.len(), SeqCst);
+          }
+          None => {
+            println!("{:s}\n", b.to_str());
+            print!("{:s}", unused);
+          }
+        }
+      }
+
+      fresh || samples == 0
     };
 
-    for (i, b) in boards.iter().enumerate() {
-      outputFile.write_str(format!("-- Solution {:u} --\n", i + 1));
-      outputFile.write_str(format!("{:s}\n\n", b.to_str()));
+    if samples > 0 {
+      let mut matrix = buildMatrix(&placements, &cols);
+      let mut pruners = pruners.iter().map(|p| p.boxClone()).collect();
+      let mut rng: IsaacRng = SeedableRng::from_seed(&[seed]);
+
+      sampleSolutions(&mut matrix, samples, &mut pruners, &heuristic, &limits, 
+                      &mut rng, &write)
+    } else {
+      runSearch(&placements, &cols, limits, threads, deterministic, split,
+                checkpointer, &resume, progress, &mut stats, &pruners, &heuristic, &write)
     }
-  } else {
-    for b in boards.iter() { println!("{:s}\n", b.to_str()); }
+  };
+
+  if samples > 0 && status == Searching {
+    stderr().write_line(format!("warning: only found {:u} different solutions to sample", 
+                                solutionsNum));
   }
+
+  saveStats(&parser, &stats, solutionsNum, status);
+
+  reportStatus(status, solutionsNum);
+
+  if status == Complete && solutionsNum == 0 {
+    stderr().write_line("no solutions (run with --diagnose to look for a reason)");
+  }
+
+  debug!("Solutions Found: {:u}", solutionsNum);
+  debug!("Solutions Written: {:u}", tally.load(SeqCst));
 }
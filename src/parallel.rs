@@ -0,0 +1,208 @@
+use std::mem;
+use std::vec::Vec;
+use std::comm::channel;
+use std::sync::atomics::{AtomicUint, SeqCst};
+use sync::Arc;
+use time::precise_time_ns;
+use dlx::Matrix;
+use cancel::CancelToken;
+use prune::Prune;
+use heuristic::Heuristic;
+use solve::{Solutions, Limits, Status, Searching, Complete, Cancelled, TimedOut};
+
+
+/// Messages sent from the worker tasks back to the caller
+enum Message {
+  /// A solution found while exploring subtree `uint`
+  Found(uint, Vec<uint>),
+  /// Subtree `uint` has been explored, or the search of 
+  /// it was stopped
+  Finished(uint, Status)
+}
+
+
+/// Finds the rows chosen in the first `depth` levels of the search
+/// tree, in the same order the sequential search visits them. Each
+/// prefix is the root of an independent subtree.
+fn splitTree(matrix: &mut Matrix, heuristic: &~Heuristic:Send, depth: uint,
+             current: &mut Vec<uint>, prefixes: &mut Vec<Vec<uint>>) {
+  if depth == 0 { prefixes.push(current.clone()); return }
+
+  let c = match heuristic.choose(matrix) {
+    Some(c) => c,
+    None => { prefixes.push(current.clone()); return }
+  };
+
+  let mut r = matrix.down(c);
+
+  while r != c {
+    matrix.select(r);
+    current.push(matrix.row(r));
+
+    splitTree(matrix, heuristic, depth - 1, current, prefixes);
+
+    current.pop();
+    matrix.unselect(r);
+
+    r = matrix.down(r);
+  }
+}
+
+
+/// Splits the search tree at its first one or two levels, and
+/// explores the subtrees on `threads` tasks. Each task works on
+/// its own copy of the matrix.
+///
+/// If `deterministic` is set, solutions are passed to `success` in
+/// the same order as the sequential search, by holding back solutions
+/// from a subtree until every subtree before it has finished.
+/// Otherwise they are passed on as soon as they are found.
+///
+/// Each task runs its own copy of the `pruners`, and picks columns
+/// with its own copy of the `heuristic`. If `split` is set, each task
+/// solves the regions of its subtrees separately.
+///
+/// Every task shares the limits of the search, and checks them before
+/// taking each subtree. If one of them is cancelled or times out, the
+/// others are stopped, and the solutions found so far are passed on.
+///
+/// Returns the number of solutions passed to `success`, and the state
+/// the search stopped in.
+pub fn solveParallel(matrix: Matrix, threads: uint, limits: Limits,
+                     deterministic: bool, split: bool, pruners: &Vec<~Prune:Send>,
+                     heuristic: &~Heuristic:Send,
+                     success: &|&Vec<uint>| -> bool) -> (uint, Status) {
+  let mut matrix = matrix;
+  let mut prefixes = Vec::new();
+
+  // Split one level down, unless that leaves too few
+  // subtrees to keep every task busy
+  splitTree(&mut matrix, heuristic, 1, &mut Vec::new(), &mut prefixes);
+
+  if prefixes.len() < threads * 4 {
+    prefixes.clear();
+    splitTree(&mut matrix, heuristic, 2, &mut Vec::new(), &mut prefixes);
+  }
+
+  debug!("Subtrees: {:u}", prefixes.len());
+
+  let numTasks = prefixes.len();
+  let prefixes = Arc::new(prefixes);
+  let maxSolutions = limits.maxSolutions;
+  let next = Arc::new(AtomicUint::new(0));
+  let (tx, rx) = channel();
+
+  // Cancelled to stop the other tasks early
+  let stop = match limits.cancel {
+    Some(ref token) => token.child(),
+    None => CancelToken::new()
+  };
+
+  for _ in range(0, threads) {
+    let tx = tx.clone();
+    let prefixes = prefixes.clone();
+    let next = next.clone();
+    let stop = stop.clone();
+    let matrix = matrix.clone();
+    let limits = Limits { cancel: Some(stop.clone()), ..limits.clone() };
+    let pruners: Vec<~Prune:Send> = pruners.iter().map(|p| p.boxClone()).collect();
+    let heuristic = heuristic.boxClone();
+
+    spawn(proc() {
+      loop {
+        let t = next.fetch_add(1, SeqCst);
+
+        if t >= numTasks { break }
+
+        // A subtree can be too small for its search to ever check
+        // the limits, so they are checked before each one is taken
+        let stopped = match limits.deadline {
+          _ if stop.isCancelled() => Some(Cancelled),
+          Some(deadline) if precise_time_ns() > deadline => Some(TimedOut),
+          _ => None
+        };
+
+        match stopped {
+          Some(status) => { tx.try_send(Finished(t, status)); break }
+          None => ()
+        }
+
+        let prefix = (*prefixes).get(t).clone();
+        let mut solutions = Solutions::new(matrix.clone(), prefix, limits.clone());
+
+        for p in pruners.iter() { solutions.pruneWith(p.boxClone()); }
+        solutions.chooseWith(heuristic.boxClone());
+        if split { solutions.splitRegions(); }
+
+        for solution in solutions.by_ref() {
+          if !tx.try_send(Found(t, solution)) { break }
+        }
+
+        if !tx.try_send(Finished(t, solutions.status())) { break }
+      }
+    });
+  }
+
+  drop(tx);
+
+  // Solutions held back from subtrees that finished out of order
+  let mut pending: Vec<Vec<Vec<uint>>> = Vec::from_fn(numTasks, |_| Vec::new());
+  let mut finished = Vec::from_elem(numTasks, false);
+  let mut emit = 0;
+  let mut solutions = 0;
+  let mut status = Searching;
+
+  'recv: loop {
+    let message = match rx.recv_opt() {
+      Some(m) => m,
+      None => { status = Complete; break }
+    };
+
+    match message {
+      Found(t, solution) => {
+        if !deterministic || t == emit {
+          solutions += 1;
+          if !(*success)(&solution) || solutions == maxSolutions { break 'recv }
+        } else {
+          pending.get_mut(t).push(solution);
+        }
+      }
+      Finished(t, Complete) | Finished(t, Searching) => {
+        *finished.get_mut(t) = true;
+
+        // Flush every subtree that can now be passed on in order
+        while emit < numTasks && *finished.get(emit) {
+          emit += 1;
+
+          if emit < numTasks {
+            let held = mem::replace(pending.get_mut(emit), Vec::new());
+
+            for solution in held.move_iter() {
+              solutions += 1;
+              if !(*success)(&solution) || solutions == maxSolutions { break 'recv }
+            }
+          }
+        }
+      }
+      Finished(_, stopped) => {
+        status = stopped;
+        break 'recv
+      }
+    }
+  }
+
+  stop.cancel();
+
+  // Pass on the solutions that were held back from 
+  // the subtrees that did not get to finish
+  if status != Complete && status != Searching {
+    'flush: for held in pending.move_iter() {
+      for solution in held.move_iter() {
+        solutions += 1;
+        if !(*success)(&solution) || solutions == maxSolutions { break 'flush }
+      }
+    }
+  }
+
+  (solutions, status)
+}
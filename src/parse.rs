@@ -1,9 +1,242 @@
+use std::uint;
 use std::vec::Vec;
 use std::num::abs;
+use std::char::is_digit;
 use std::io::File;
 use pentomino::{Pentomino, System};
 
 
+/// Marks drawn touching a piece, that change how it is used
+/// rather than being part of its shape
+static MANDATORY: char = '!';
+static OPTIONAL: char = '?';
+
+/// Lines that start with this state rules about the
+/// pieces instead of drawing squares
+static RULE: char = ';';
+
+
+/// A limit on how many pieces of a group a solution uses, from 
+/// a line `; group <mark> <min> [<max>]`, where `max` is `*` for 
+/// no limit, or left out to use exactly `min` pieces. The group
+/// is the pieces drawn touching `mark` (each copy of a piece counts).
+#[deriving(Clone, Show)]
+pub struct GroupRule {
+  pub mark: char,
+  pub min: uint,
+  pub max: uint
+}
+
+
+/// A piece fixed in place, from a line `; pin <mark> <turns>[f] <x> <y>`.
+/// The piece drawn touching `mark` is reflected if `f` is given, then 
+/// turned right `turns` times, and its top left corner is put on the
+/// square `x`, `y` of the board (counting from 0 at the top left).
+#[deriving(Clone, Show)]
+pub struct PinRule {
+  pub mark: char,
+  pub turns: uint,
+  pub reflected: bool,
+  pub x: uint,
+  pub y: uint
+}
+
+
+/// Squares that pieces may not cover, from a line 
+/// `; forbid <mark> <x> <y> [<x1> <y1>]`. The pieces drawn touching
+/// `mark` may not cover the square `x`, `y`, or any square in the 
+/// rectangle from `x`, `y` to `x1`, `y1`.
+#[deriving(Clone, Show)]
+pub struct ForbidRule {
+  pub mark: char,
+  pub x0: uint,
+  pub y0: uint,
+  pub x1: uint,
+  pub y1: uint
+}
+
+
+/// The colour of the squares drawn with a character, from a line
+/// `; colour <char> <name>`. Squares of pieces can only cover squares
+/// of the board with the same colour. Characters without a colour 
+/// are a colour of their own.
+#[deriving(Clone, Show)]
+pub struct ColourRule {
+  pub square: char,
+  pub colour: ~str
+}
+
+
+/// Rules stated in the puzzle file
+///
+///   * `groups` - limits on how many pieces of each group are used
+///   * `pins` - pieces fixed in place
+///   * `forbids` - squares that pieces may not cover
+///   * `colours` - the legend of colours the squares are drawn in
+///   * `wildcards` - characters that match any colour, from lines
+///                   `; wildcard <char>`
+///   * `backs` - the character a square of a piece shows once the 
+///               piece is turned over, from lines `; back <char> <char>`
+///               (a square shows the same character by default)
+#[deriving(Clone, Show)]
+pub struct Rules {
+  pub groups: Vec<GroupRule>,
+  pub pins: Vec<PinRule>,
+  pub forbids: Vec<ForbidRule>,
+  pub colours: Vec<ColourRule>,
+  pub wildcards: Vec<char>,
+  pub backs: Vec<(char, char)>
+}
+
+
+impl Rules {
+  fn new() -> Rules {
+    Rules { 
+      groups: Vec::new(), pins: Vec::new(), forbids: Vec::new(),
+      colours: Vec::new(), wildcards: Vec::new(), backs: Vec::new()
+    }
+  }
+  /// Whether `c` is a mark declared by a rule
+  fn isMark(&self, c: char) -> bool {
+    self.groups.iter().any(|g| g.mark == c) ||
+    self.pins.iter().any(|p| p.mark == c) ||
+    self.forbids.iter().any(|f| f.mark == c)
+  }
+}
+
+
+/// Parses a number in a rule
+fn parseNumber(word: Option<&str>, line: &str) -> uint {
+  match word.and_then(|w| from_str(w)) {
+    Some(n) => n,
+    None => fail!("invalid rule `{:s}`!", line)
+  }
+}
+
+
+/// Parses the mark a rule applies to
+fn parseMark(word: Option<&str>, line: &str) -> char {
+  match word {
+    Some(w) if w.char_len() == 1 => w.char_at(0),
+    _ => fail!("invalid rule `{:s}`!", line)
+  }
+}
+
+
+/// Parses a line stating a rule, without the leading `;`
+fn parseRule(line: &str, rules: &mut Rules) {
+  let mut words = line.words();
+
+  match words.next() {
+    Some("group") => {
+      let mark = parseMark(words.next(), line);
+      let min = parseNumber(words.next(), line);
+      let max = match words.next() {
+        Some("*") => uint::MAX,
+        None => min,
+        w => parseNumber(w, line)
+      };
+
+      if min > max { fail!("invalid rule `{:s}`!", line) }
+
+      rules.groups.push(GroupRule { mark: mark, min: min, max: max });
+    }
+    Some("pin") => {
+      let mark = parseMark(words.next(), line);
+      let (turns, reflected) = match words.next() {
+        Some(w) if w.ends_with("f") => (parseNumber(Some(w.slice_to(w.len() - 1)), line), true),
+        w => (parseNumber(w, line), false)
+      };
+      let x = parseNumber(words.next(), line);
+      let y = parseNumber(words.next(), line);
+
+      rules.pins.push(PinRule { mark: mark, turns: turns, reflected: reflected, x: x, y: y });
+    }
+    Some("forbid") => {
+      let mark = parseMark(words.next(), line);
+      let x0 = parseNumber(words.next(), line);
+      let y0 = parseNumber(words.next(), line);
+      let (x1, y1) = match words.next() {
+        None => (x0, y0),
+        w => (parseNumber(w, line), parseNumber(words.next(), line))
+      };
+
+      if x0 > x1 || y0 > y1 { fail!("invalid rule `{:s}`!", line) }
+
+      rules.forbids.push(ForbidRule { mark: mark, x0: x0, y0: y0, x1: x1, y1: y1 });
+    }
+    Some("colour") => {
+      let square = parseMark(words.next(), line);
+      let colour = match words.next() {
+        Some(w) => w.to_owned(),
+        None => fail!("invalid rule `{:s}`!", line)
+      };
+
+      rules.colours.push(ColourRule { square: square, colour: colour });
+    }
+    Some("wildcard") => rules.wildcards.push(parseMark(words.next(), line)),
+    Some("back") => {
+      let front = parseMark(words.next(), line);
+      let back = parseMark(words.next(), line);
+
+      rules.backs.push((front, back));
+    }
+    Some(_) => fail!("unknown rule `{:s}`!", line),
+    None => ()
+  }
+
+  if words.next().is_some() { fail!("invalid rule `{:s}`!", line) }
+}
+
+
+/// How a piece may be used, from the marks drawn next to it
+///
+///   * `mandatory` - every solution must use the piece (every copy of
+///                   it). Pieces are optional unless they are marked 
+///                   with a `!`, and can be marked with a `?` to say 
+///                   so explicitly.
+///   * `copies` - number of copies of the piece, given by a number 
+///                drawn next to it (one if there is no number)
+///   * `labels` - the marks of the rules that apply to the piece
+#[deriving(Clone, Show)]
+pub struct Marks {
+  pub mandatory: bool,
+  pub copies: uint,
+  pub labels: Vec<char>
+}
+
+
+/// Removes the marks from a group of points, returning them
+fn takeMarks(system: &mut System, rules: &Rules) -> Marks {
+  let mut marks = Marks { mandatory: false, copies: 1, labels: Vec::new() };
+  let mut digits = Vec::new();
+
+  system.retain(|&(x, y, c)| {
+    match c.to_char() {
+      MANDATORY => { marks.mandatory = true; false }
+      OPTIONAL => { marks.mandatory = false; false }
+      d if is_digit(d) => { digits.push((y, x, d)); false }
+      m if rules.isMark(m) => { marks.labels.push(m); false }
+      _ => true
+    }
+  });
+
+  // Read the digits left to right, top to bottom
+  if digits.len() > 0 {
+    digits.as_mut_slice().sort();
+
+    let number: ~str = digits.iter().map(|&(_, _, d)| d).collect();
+
+    marks.copies = match from_str(number) {
+      Some(n) if n > 0 => n,
+      _ => fail!("invalid number of copies `{:s}`!", number)
+    };
+  }
+
+  marks
+}
+
+
 /// Discovers all adjacent points
 /// in a System from a given coordinate
 #[inline]
@@ -30,8 +263,10 @@ fn discoverPoints(startX: uint, startY: uint, system: &mut System,
 
 /// Takes in a path, and parses
 /// a file at the path, finding all 
-/// valid Pentominoes in the file.
-pub fn parseFile(path: &Path) -> Vec<Pentomino> {
+/// valid Pentominoes in the file, 
+/// the marks next to each one, 
+/// and the rules about them.
+pub fn parseFile(path: &Path) -> (Vec<Pentomino>, Vec<Marks>, Rules) {
   if !path.exists() { fail!("file not found!") }
 
   let mut file = File::open(path);
@@ -40,18 +275,31 @@ pub fn parseFile(path: &Path) -> Vec<Pentomino> {
   let mut y = 0;
   let mut points: System = Vec::new();
   let mut pentominoes: Vec<Pentomino> = Vec::new();
+  let mut marks: Vec<Marks> = Vec::new();
+  let mut rules = Rules::new();
+  let mut rule: Option<~str> = None;
+  let mut blank = true;
 
   loop {
     match file.read_byte() {
       Ok(b) => {
         match b as char {
           '\n' => {
+            match rule.take() {
+              Some(line) => parseRule(line.as_slice(), &mut rules),
+              None => ()
+            }
+
             x = 0;
             y = y + 1;
+            blank = true;
           }
+          c if rule.is_some() => rule.get_mut_ref().push_char(c),
+          RULE if blank => rule = Some(~""),
           c => {
             if c != ' ' {
               points.push((x, y, c.to_ascii()));
+              blank = false;
             }
 
             x = x + 1;
@@ -63,6 +311,11 @@ pub fn parseFile(path: &Path) -> Vec<Pentomino> {
     }
   }
 
+  match rule.take() {
+    Some(line) => parseRule(line.as_slice(), &mut rules),
+    None => ()
+  }
+
   if points.len() == 0 {
     fail!("invalid (possibly empty) text file!")
   }
@@ -73,11 +326,16 @@ pub fn parseFile(path: &Path) -> Vec<Pentomino> {
         let (coorX, coorY, _) = point;
         let mut pentomino = Vec::from_elem(1, point);
         discoverPoints(coorX, coorY, &mut points, &mut pentomino);
+
+        marks.push(takeMarks(&mut pentomino, &rules));
+
+        if pentomino.len() == 0 { fail!("mark not next to any piece!") }
+
         pentominoes.push(Pentomino::newFromSystem(pentomino));
       }
       None => break
     }
   }
 
-  pentominoes
+  (pentominoes, marks, rules)
 }
@@ -4,6 +4,7 @@ use std::num::abs;
 use std::slice::Items;
 use std::vec::{Vec, MoveItems};
 use std::fmt::{Show, Formatter, Result};
+use colour::Palette;
 
 
 
@@ -262,19 +263,44 @@ impl<'a> Pentomino<'a> {
     self.doTransformation(self.dimY, self.dimX,
       |x, y| { (x * self.dimY) + self.dimY - y - 1 })
   }
+  /// Returns a new Pentomino with the character
+  /// of each filled square changed by `fun`
+  pub fn recolour(&self, fun: |Ascii| -> Ascii) -> Pentomino {
+    let squares = self.squares.iter().map(|sq| match *sq {
+      Filled(c) => Filled(fun(c)),
+      Empty => Empty
+    }).collect();
+
+    Pentomino {
+      dimX: self.dimX,
+      dimY: self.dimY,
+      size: self.size,
+      squares: squares
+    }
+  }
+  /// Returns a new Pentomino which is the current one 
+  /// reflected over the X axis (if `reflected` is set),
+  /// then rotated right 90 degrees `turns` times
+  pub fn orient(&self, turns: uint, reflected: bool) -> Pentomino {
+    let mut piece = if reflected { self.reflectX() } else { self.clone() };
+    for _ in range(0, turns % 4) { piece = piece.rotateRight(); }
+    piece
+  }
 }
 
 
 impl<'a> Pentomino<'a> {
   /// Returns whether or not a piece can be placed on 
-  /// the piece
+  /// the piece, with the colours of the squares it
+  /// covers matching in `palette`
   pub fn canPlace(&self, p: &Pentomino, offsetX: uint, 
-                  offsetY: uint) -> bool {
+                  offsetY: uint, palette: &Palette) -> bool {
     let mut placements: uint = 0;
 
     for (x, y, c) in p.filled() {
       match self.get_opt(x + offsetX, y + offsetY) {
-        Some(sq) => if sq.to_ascii() == c { placements += 1 },
+        Some(&Filled(sq)) => if palette.matches(sq, c) { placements += 1 },
+        Some(&Empty) => (),
         None => ()
       }
     }
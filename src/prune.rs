@@ -0,0 +1,406 @@
+use std::cmp;
+use std::mem::swap;
+use std::vec::Vec;
+use dlx::Matrix;
+use bitset::BitSet;
+use pentomino::{Pentomino, Filled};
+
+
+/// A check run at every node of the search, that can cut
+/// branches which can not lead to a solution
+pub trait Prune {
+  /// Whether no solution can be found below the current node
+  fn prune(&mut self, matrix: &Matrix) -> bool;
+  /// Copies the pruner, so each task of a search can have its own
+  fn boxClone(&self) -> ~Prune:Send;
+}
+
+
+/// Finds the squares next to each square of a `dimX` x `dimY` grid
+/// that are in `inside`. Diagonal squares are included if `diagonal`
+/// is set.
+fn neighbours(dimX: uint, dimY: uint, inside: |uint, uint| -> bool,
+              diagonal: bool) -> Vec<Vec<uint>> {
+  Vec::from_fn(dimX * dimY, |i| {
+    let (x, y) = (i % dimX, i / dimX);
+    let mut next = Vec::new();
+
+    for dy in range(-1, 2) {
+      for dx in range(-1, 2) {
+        if dx == 0 && dy == 0 { continue }
+        if !diagonal && dx != 0 && dy != 0 { continue }
+
+        let (x0, y0) = (x as int + dx, y as int + dy);
+
+        if x0 < 0 || y0 < 0 || x0 >= dimX as int || y0 >= dimY as int { continue }
+        if inside(x0 as uint, y0 as uint) { next.push(y0 as uint * dimX + x0 as uint); }
+      }
+    }
+
+    next
+  })
+}
+
+
+/// Whether the squares of a piece are connected through their
+/// sides, not only through their corners
+fn isEdgeConnected(piece: &Pentomino) -> bool {
+  let inside = |x: uint, y: uint| match piece.get_opt(x, y) {
+    Some(&Filled(_)) => true,
+    _ => false
+  };
+  let next = neighbours(piece.dimX, piece.dimY, inside, false);
+  let start = match piece.filled().next() {
+    Some((x, y, _)) => piece.getIndex(x, y),
+    None => return true
+  };
+
+  let mut seen = Vec::from_elem(piece.area(), false);
+  let mut stack = Vec::from_elem(1, start);
+  let mut count = 0;
+
+  *seen.get_mut(start) = true;
+
+  loop {
+    let i = match stack.pop() { Some(i) => i, None => break };
+    count += 1;
+
+    for j in next.get(i).iter() {
+      if !*seen.get(*j) { *seen.get_mut(*j) = true; stack.push(*j); }
+    }
+  }
+
+  count == piece.size()
+}
+
+
+/// Cuts a branch when the uncovered squares of the board split into
+/// a region that no set of the pieces left can exactly fill. For
+/// pentominoes this catches regions whose size is not a multiple
+/// of 5.
+///
+/// Attributes
+///   * offset - number of pieces (the columns before the squares)
+///   * board - whether each square is part of the board
+///   * next - the board squares next to each square. Regions are
+///            joined through corners if any piece is only joined
+///            through corners, so no piece can cross two regions.
+///   * sizes - the number of squares in each piece
+///   * seen - squares already counted in a region (reused)
+///   * stack - squares left to visit in the region (reused)
+///   * sums - region sizes the pieces left can fill (reused)
+///
+#[deriving(Clone)]
+pub struct RegionPrune {
+  offset: uint,
+  board: Vec<bool>,
+  next: Vec<Vec<uint>>,
+  sizes: Vec<uint>,
+  seen: Vec<bool>,
+  stack: Vec<uint>,
+  sums: Vec<bool>
+}
+
+
+impl RegionPrune {
+  pub fn new(board: &Pentomino, pieces: &Vec<Pentomino>) -> RegionPrune {
+    let diagonal = !pieces.iter().all(|p| isEdgeConnected(p));
+    let squares: Vec<bool> = board.iter().map(|sq| match *sq {
+      Filled(_) => true,
+      _ => false
+    }).collect();
+    let next = neighbours(board.dimX, board.dimY, |x, y| *squares.get(board.getIndex(x, y)),
+                          diagonal);
+
+    RegionPrune {
+      offset: pieces.len(),
+      board: squares,
+      next: next,
+      sizes: pieces.iter().map(|p| p.size()).collect(),
+      seen: Vec::from_elem(board.area(), false),
+      stack: Vec::new(),
+      sums: Vec::from_elem(board.area() + 1, false)
+    }
+  }
+  /// Whether square `i` is on the board, and still uncovered
+  fn open(&self, matrix: &Matrix, i: uint) -> bool {
+    *self.board.get(i) && matrix.remaining(matrix.header(self.offset + i)) > 0
+  }
+  /// Finds every region size that some set of the pieces left
+  /// adds up to
+  fn findSums(&mut self, matrix: &Matrix) {
+    let area = self.sums.len() - 1;
+
+    for s in self.sums.mut_iter() { *s = false; }
+    *self.sums.get_mut(0) = true;
+
+    for (p, size) in self.sizes.iter().enumerate() {
+      let h = matrix.header(p);
+      let copies = if matrix.len(h) > 0 { cmp::min(matrix.remaining(h), area) } else { 0 };
+
+      for _ in range(0, copies) {
+        for s in range(*size, area + 1).rev() {
+          if *self.sums.get(s - *size) { *self.sums.get_mut(s) = true; }
+        }
+      }
+    }
+  }
+}
+
+
+impl Prune for RegionPrune {
+  fn prune(&mut self, matrix: &Matrix) -> bool {
+    self.findSums(matrix);
+
+    for s in self.seen.mut_iter() { *s = false; }
+
+    for i in range(0, self.seen.len()) {
+      if *self.seen.get(i) || !self.open(matrix, i) { continue }
+
+      // Flood fill the region around square `i`
+      let mut size = 0;
+
+      *self.seen.get_mut(i) = true;
+      self.stack.push(i);
+
+      loop {
+        let j = match self.stack.pop() { Some(j) => j, None => break };
+        size += 1;
+
+        for k in range(0, self.next.get(j).len()) {
+          let n = *self.next.get(j).get(k);
+
+          if !*self.seen.get(n) && self.open(matrix, n) {
+            *self.seen.get_mut(n) = true;
+            self.stack.push(n);
+          }
+        }
+      }
+
+      if !*self.sums.get(size) { return true }
+    }
+
+    false
+  }
+  fn boxClone(&self) -> ~Prune:Send {
+    ~self.clone() as ~Prune:Send
+  }
+}
+
+
+/// A way of colouring the squares of a board with `k` colours, 
+/// where square `x`, `y` has the colour `(a * x + b * y) mod k`.
+/// The checkerboard is `a = b = 1`, `k = 2`.
+#[deriving(Clone, Eq, Show)]
+pub struct Colouring {
+  pub a: uint,
+  pub b: uint,
+  pub k: uint
+}
+
+
+impl Colouring {
+  pub fn checkerboard() -> Colouring {
+    Colouring { a: 1, b: 1, k: 2 }
+  }
+  /// The colourings with `k` colours in stripes along the rows, 
+  /// the columns, and both diagonals
+  pub fn stripes(k: uint) -> Vec<Colouring> {
+    let mut colourings = Vec::new();
+
+    for &(a, b) in [(1, 0), (0, 1), (1, 1), (1, k - 1)].iter() {
+      let colouring = Colouring { a: a, b: b, k: k };
+      if !colourings.contains(&colouring) { colourings.push(colouring); }
+    }
+
+    colourings
+  }
+  /// The colour of square `x`, `y`
+  pub fn colour(&self, x: uint, y: uint) -> uint {
+    (self.a * x + self.b * y) % self.k
+  }
+}
+
+
+/// Cuts a branch when the pieces left can not cover the uncovered
+/// squares of each colour. With the checkerboard, a piece covers a
+/// different number of black and white squares depending on where
+/// it is placed, so this finds, for each colour, every pair of 
+/// (squares covered, squares of the colour covered) that the pieces
+/// left add up to, and checks the uncovered squares are one of them.
+///
+/// Attributes
+///   * offset - number of pieces (the columns before the squares)
+///   * k - number of colours
+///   * colour - the colour of each square, or None off the board
+///   * sizes - the number of squares in each piece
+///   * mandatory - whether each piece must be used
+///   * counts - uncovered squares of each colour (reused)
+///   * row - squares of each colour a row covers (reused)
+///   * options - the numbers of squares of each colour that the rows
+///               left for each piece can cover (reused)
+///   * table, next - the pairs the pieces add up to (reused)
+///
+#[deriving(Clone)]
+pub struct ColourPrune {
+  offset: uint,
+  k: uint,
+  colour: Vec<Option<uint>>,
+  sizes: Vec<uint>,
+  mandatory: Vec<bool>,
+  counts: Vec<uint>,
+  row: Vec<uint>,
+  options: Vec<Vec<Vec<uint>>>,
+  table: Vec<bool>,
+  next: Vec<bool>
+}
+
+
+impl ColourPrune {
+  /// Creates a pruner for `colouring`, where the pieces in the
+  /// `primary` columns must be used
+  pub fn new(board: &Pentomino, pieces: &Vec<Pentomino>, primary: &BitSet,
+             colouring: Colouring) -> ColourPrune {
+    let k = colouring.k;
+    let states = (board.size() + 1) * (board.size() + 1);
+
+    ColourPrune {
+      offset: pieces.len(),
+      k: k,
+      colour: board.range().map(|i| {
+        let (x, y) = board.getCoordinates(i);
+
+        match *board.get(x, y) {
+          Filled(_) => Some(colouring.colour(x, y)),
+          _ => None
+        }
+      }).collect(),
+      sizes: pieces.iter().map(|p| p.size()).collect(),
+      mandatory: range(0, pieces.len()).map(|p| primary.get(p)).collect(),
+      counts: Vec::from_elem(k, 0u),
+      row: Vec::from_elem(k, 0u),
+      options: Vec::from_fn(pieces.len(), |_| Vec::from_fn(k, |_| Vec::new())),
+      table: Vec::from_elem(states, false),
+      next: Vec::from_elem(states, false)
+    }
+  }
+  /// Finds how many squares of each colour the rows left for 
+  /// each piece cover
+  fn findOptions(&mut self, matrix: &Matrix) {
+    let first = matrix.header(self.offset);
+
+    for p in range(0, self.offset) {
+      for o in self.options.get_mut(p).mut_iter() { o.truncate(0); }
+
+      let h = matrix.header(p);
+      if matrix.remaining(h) == 0 { continue }
+
+      let mut n = matrix.down(h);
+
+      while n != h {
+        for c in self.row.mut_iter() { *c = 0; }
+
+        let mut j = n;
+
+        loop {
+          let c = matrix.col(j);
+
+          if c >= first {
+            match *self.colour.get(c - first) {
+              Some(i) => *self.row.get_mut(i) += 1,
+              None => ()
+            }
+          }
+
+          j = matrix.right(j);
+          if j == n { break }
+        }
+
+        for i in range(0, self.k) {
+          let count = *self.row.get(i);
+          let options = self.options.get_mut(p).get_mut(i);
+          if !options.contains(&count) { options.push(count); }
+        }
+
+        n = matrix.down(n);
+      }
+    }
+  }
+  /// Whether the pieces left can cover exactly `total` squares, 
+  /// `open` of which have colour `i`
+  fn fits(&mut self, matrix: &Matrix, i: uint, total: uint, open: uint) -> bool {
+    let width = open + 1;
+    let states = (total + 1) * width;
+
+    for t in self.table.mut_slice_to(states).mut_iter() { *t = false; }
+    *self.table.get_mut(0) = true;
+
+    for p in range(0, self.offset) {
+      let h = matrix.header(p);
+      let size = *self.sizes.get(p);
+      let mandatory = *self.mandatory.get(p);
+      let remaining = matrix.remaining(h);
+
+      if remaining == 0 { continue }
+      if size > total { 
+        if mandatory { return false } else { continue } 
+      }
+
+      // Optional copies can be left out, so only as many as
+      // fit in the squares left need to be tried
+      let copies = if mandatory { remaining } else { cmp::min(remaining, total / size) };
+
+      for _ in range(0, copies) {
+        for t in range(0, states) { 
+          *self.next.get_mut(t) = !mandatory && *self.table.get(t); 
+        }
+
+        for s in range(0, total + 1 - size) {
+          for c in range(0, width) {
+            if !*self.table.get(s * width + c) { continue }
+
+            for o in self.options.get(p).get(i).iter() {
+              if c + *o < width { *self.next.get_mut((s + size) * width + c + *o) = true; }
+            }
+          }
+        }
+
+        swap(&mut self.table, &mut self.next);
+      }
+    }
+
+    *self.table.get(total * width + open)
+  }
+}
+
+
+impl Prune for ColourPrune {
+  fn prune(&mut self, matrix: &Matrix) -> bool {
+    let mut total = 0;
+
+    for c in self.counts.mut_iter() { *c = 0; }
+
+    for sq in range(0, self.colour.len()) {
+      match *self.colour.get(sq) {
+        Some(i) if matrix.remaining(matrix.header(self.offset + sq)) > 0 => {
+          *self.counts.get_mut(i) += 1;
+          total += 1;
+        }
+        _ => ()
+      }
+    }
+
+    self.findOptions(matrix);
+
+    // The squares of the last colour are the ones left over
+    for i in range(0, self.k - 1) {
+      let open = *self.counts.get(i);
+      if !self.fits(matrix, i, total, open) { return true }
+    }
+
+    false
+  }
+  fn boxClone(&self) -> ~Prune:Send {
+    ~self.clone() as ~Prune:Send
+  }
+}
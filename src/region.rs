@@ -0,0 +1,185 @@
+use std::cmp;
+use std::uint;
+use std::vec::Vec;
+use bitset::BitSet;
+use dlx::Matrix;
+
+
+/// Sets of columns that have been joined together, kept as a forest
+/// where each set is a tree, named by its root
+struct UnionFind {
+  parent: Vec<uint>
+}
+
+
+impl UnionFind {
+  /// Puts each of `n` columns in a set of its own
+  fn new(n: uint) -> UnionFind {
+    UnionFind { parent: Vec::from_fn(n, |i| i) }
+  }
+  /// Puts each column back in a set of its own
+  fn reset(&mut self) {
+    for (i, p) in self.parent.mut_iter().enumerate() { *p = i; }
+  }
+  /// The root of the set `i` is in, halving the path to it
+  /// on the way so later calls are quicker
+  fn find(&mut self, i: uint) -> uint {
+    let mut i = i;
+
+    while *self.parent.get(i) != i {
+      let grandparent = *self.parent.get(*self.parent.get(i));
+      *self.parent.get_mut(i) = grandparent;
+      i = grandparent;
+    }
+
+    i
+  }
+  /// Joins the sets `a` and `b` are in
+  fn union(&mut self, a: uint, b: uint) {
+    let (ra, rb) = (self.find(a), self.find(b));
+    if ra != rb { *self.parent.get_mut(ra) = rb; }
+  }
+}
+
+
+/// Splits what is left of a matrix into regions that can be solved
+/// separately, because no row that is still linked has columns in
+/// two of them, other than free columns. This happens when walls or
+/// the pieces placed so far cut the board into chambers, each with
+/// pieces of its own, or given enough copies of the pieces to share.
+///
+/// Groups limit how many times their columns are covered between
+/// them, wherever they are on the board, so a matrix with groups is
+/// never split.
+///
+/// Attributes
+///   * sets - the columns joined by the rows left
+///   * open - the uncovered primary columns
+///   * free - whether each secondary column has been found to be free
+///
+pub struct Splitter {
+  sets: UnionFind,
+  open: BitSet,
+  free: Vec<Option<bool>>
+}
+
+
+impl Splitter {
+  /// Creates a splitter for the headers of `matrix`
+  pub fn new(matrix: &Matrix) -> Splitter {
+    let n = matrix.numHeaders();
+
+    Splitter {
+      sets: UnionFind::new(n),
+      open: BitSet::new(n),
+      free: Vec::from_elem(n, None)
+    }
+  }
+  /// Whether the secondary column of header `h` can never be covered
+  /// too many times by the rows left, so that it does not tie together
+  /// the regions its rows are in.
+  ///
+  /// Rows that are chosen together cover disjoint squares, so they can
+  /// use no more than the `squares` left to cover, over the fewest
+  /// squares any one of them covers. This frees the pieces of a tiling
+  /// that are given enough copies to cover the whole board.
+  fn isFree(&mut self, matrix: &Matrix, h: uint, squares: uint) -> bool {
+    match *self.free.get(h) {
+      Some(free) => return free,
+      None => ()
+    }
+
+    let mut least = uint::MAX;
+    let mut i = matrix.down(h);
+
+    while i != h {
+      let mut size = 0;
+      let mut j = matrix.right(i);
+
+      while j != i {
+        let k = matrix.col(j);
+        if self.open.get(k) && matrix.capacity(k) == 1 { size += 1; }
+        j = matrix.right(j);
+      }
+
+      least = cmp::min(least, size);
+      i = matrix.down(i);
+    }
+
+    let free = least == uint::MAX || (least > 0 && matrix.remaining(h) >= squares / least);
+    *self.free.get_mut(h) = Some(free);
+    free
+  }
+  /// Splits the headers of the uncovered primary columns in `within`
+  /// (or all of them) into regions, numbered in the order of their
+  /// first column. Returns no regions once they have all been covered.
+  pub fn regions(&mut self, matrix: &Matrix, within: Option<&BitSet>) -> Vec<BitSet> {
+    let n = matrix.numHeaders();
+    let root = matrix.root();
+    let mut inside = Vec::new();
+    let mut squares = 0;
+
+    self.open = BitSet::new(n);
+
+    let mut c = matrix.right(root);
+
+    while c != root {
+      self.open.set(c, true);
+      if matrix.capacity(c) == 1 { squares += 1; }
+
+      match within {
+        Some(w) if !w.get(c) => (),
+        _ => inside.push(c)
+      }
+
+      c = matrix.right(c);
+    }
+
+    if inside.len() == 0 { return Vec::new() }
+
+    if matrix.hasGroups() {
+      let mut all = BitSet::new(n);
+      for h in inside.iter() { all.set(*h, true); }
+      return Vec::from_elem(1, all)
+    }
+
+    self.sets.reset();
+    for f in self.free.mut_iter() { *f = None; }
+
+    for h in inside.iter() {
+      let mut i = matrix.down(*h);
+
+      while i != *h {
+        let mut j = matrix.right(i);
+
+        while j != i {
+          let k = matrix.col(j);
+          if self.open.get(k) || !self.isFree(matrix, k, squares) { self.sets.union(*h, k); }
+          j = matrix.right(j);
+        }
+
+        i = matrix.down(i);
+      }
+    }
+
+    let mut index: Vec<Option<uint>> = Vec::from_elem(n, None);
+    let mut regions: Vec<BitSet> = Vec::new();
+
+    for h in inside.iter() {
+      let root = self.sets.find(*h);
+
+      let i = match *index.get(root) {
+        Some(i) => i,
+        None => {
+          *index.get_mut(root) = Some(regions.len());
+          regions.push(BitSet::new(n));
+          regions.len() - 1
+        }
+      };
+
+      regions.get_mut(i).set(*h, true);
+    }
+
+    regions
+  }
+}
@@ -0,0 +1,98 @@
+use std::vec::Vec;
+use collections::hashmap::HashSet;
+use rand::Rng;
+use time::precise_time_ns;
+use dlx::Matrix;
+use prune::Prune;
+use heuristic::Heuristic;
+use estimate::probe;
+use solve::{Limits, Status, Searching, Complete, Cancelled, TimedOut};
+
+
+/// Number of restarts in a row that can fail to give a new solution
+/// before sampling gives up. Puzzles with only a few solutions run
+/// out of new ones to find.
+static MAX_FAILURES: uint = 100000;
+
+
+/// Follows a single random path from the root of the search tree.
+/// Returns the rows of the solution it reaches, if any, along with
+/// the product of the number of rows of each column on the path,
+/// which is one over the chance of the path being taken.
+fn walk<R: Rng>(matrix: &mut Matrix, pruners: &mut Vec<~Prune:Send>,
+                heuristic: &~Heuristic:Send, rng: &mut R) -> Option<(Vec<uint>, f64)> {
+  // A probe that reaches a solution estimates its weight
+  // as the number of solutions
+  let (estimate, rows) = probe(matrix, pruners, heuristic, rng);
+
+  if estimate.solutions > 0.0 { Some((rows, estimate.solutions)) } else { None }
+}
+
+
+/// Passes different solutions of `matrix` to `success`, picked close to
+/// uniformly at random, until it has accepted `samples` of them.
+/// `success` returns whether it accepted the solution, which it may
+/// not do if it is a repeat of one before in a way sampling can not
+/// see, such as a reflection of it.
+///
+/// Each solution is found by a random walk from the root of the tree,
+/// which reaches a solution with a chance of one over its weight. To
+/// even this out, a solution is only kept with a chance of its weight
+/// over the largest weight seen so far, and solutions already passed
+/// on are skipped. The walks cut the same branches as the `pruners`,
+/// and choose columns with the `heuristic`.
+///
+/// Returns the number of solutions `success` accepted, and the state
+/// sampling stopped in: Complete once it has enough solutions, or
+/// Searching if it gave up finding new ones.
+pub fn sampleSolutions<R: Rng>(matrix: &mut Matrix, samples: uint,
+                               pruners: &mut Vec<~Prune:Send>,
+                               heuristic: &~Heuristic:Send, limits: &Limits,
+                               rng: &mut R,
+                               success: &|&Vec<uint>| -> bool) -> (uint, Status) {
+  let mut found = 0;
+  let mut failures = 0;
+  let mut maxWeight = 0.0;
+  let mut seen = HashSet::new();
+
+  while found < samples {
+    if failures == MAX_FAILURES { return (found, Searching) }
+
+    match limits.cancel {
+      Some(ref token) if token.isCancelled() => return (found, Cancelled),
+      _ => ()
+    }
+
+    match limits.deadline {
+      Some(deadline) if precise_time_ns() > deadline => return (found, TimedOut),
+      _ => ()
+    }
+
+    let (solution, weight) = match walk(matrix, pruners, heuristic, rng) {
+      Some(s) => s,
+      None => { failures += 1; continue }
+    };
+
+    if weight > maxWeight { maxWeight = weight; }
+
+    let mut rows = solution.clone();
+    rows.as_mut_slice().sort();
+
+    if rng.gen::<f64>() * maxWeight > weight || seen.contains(&rows) {
+      failures += 1;
+      continue
+    }
+
+    seen.insert(rows);
+
+    if !(*success)(&solution) {
+      failures += 1;
+      continue
+    }
+
+    found += 1;
+    failures = 0;
+  }
+
+  (found, Complete)
+}
@@ -25,61 +25,70 @@ fn generateNRandomAscii(n: uint, used: &mut Vec<char>) {
 }
 
 
-/// Takes solutions in the form of a vector of 
-/// indices, and converts them each into a 
-/// Pentomino
-pub fn convertSolutions(board: &Pentomino,
-                        solutions: &Vec<Vec<uint>>, 
-                        placements: &Vec<Placement>, 
-                        offset: uint) -> Vec<Pentomino> {
-  let mut boards = Vec::with_capacity(solutions.len());
+/// Picks a distinct random letter for each of the 
+/// `offset` pieces
+pub fn generateSymbols(offset: uint) -> Vec<char> {
   let mut symbols = Vec::with_capacity(offset);
-
   generateNRandomAscii(offset, &mut symbols);
+  symbols
+}
 
-  for s in solutions.iter() {
-    let mut system: System = Vec::new();
 
-    for n in s.iter() {
-      let row = placements.get(*n); 
-      let pieceNum = *row.filled().get(0);
+/// Takes a solution in the form of a vector of 
+/// indices, and converts it into a Pentomino
+pub fn convertSolution(board: &Pentomino,
+                       solution: &Vec<uint>,
+                       placements: &Vec<Placement>,
+                       offset: uint,
+                       symbols: &Vec<char>) -> Pentomino {
+  let mut system: System = Vec::new();
 
-      assert!(pieceNum < offset)
+  for n in solution.iter() {
+    let row = placements.get(*n); 
+    let pieceNum = *row.filled().get(0);
 
-      for row0 in row.filled().slice_from(1).iter() {
-        let (x, y) = board.getCoordinates(*row0 - offset);
-        system.push((x, y, symbols.get(pieceNum).to_ascii()));
-      }
-    }
+    assert!(pieceNum < offset)
 
-    boards.push(Pentomino::newFromSystem(system));
+    for row0 in row.filled().slice_from(1).iter() {
+      let (x, y) = board.getCoordinates(*row0 - offset);
+      system.push((x, y, symbols.get(pieceNum).to_ascii()));
+    }
   }
 
-  boards
+  Pentomino::newFromSystem(system)
 }
 
 
-/// Takes a vector of Pentominos, and 
-/// removes any isometric ones
-pub fn removeIsometric(boards: &mut Vec<Pentomino>) {
-  for i in range(0, boards.len()).rev() {
-    match boards.remove(i) {
-      Some(b) => {
-        let mut present = false;
-
-        for rotation in b.rotations() {
-          for reflection in rotation.reflections() {
-            for b0 in boards.iter() {
-              if *b0 == reflection { present = true; break }
-            }
-            if present { break }
-          }
-          if present { break }
-        }
-
-        if !present { boards.push(b); } 
-      }
-      None => break
-    }
+/// Finds the pieces that a solution does not use, listing a 
+/// piece once for each of its `copies` that was left out
+pub fn unusedPieces(solution: &Vec<uint>, placements: &Vec<Placement>,
+                    copies: &Vec<uint>) -> Vec<uint> {
+  let mut left = copies.clone();
+  let mut unused = Vec::new();
+
+  for n in solution.iter() {
+    *left.get_mut(*placements.get(*n).filled().get(0)) -= 1;
+  }
+
+  for (i, n) in left.iter().enumerate() {
+    for _ in range(0, *n) { unused.push(i); }
   }
+
+  unused
+}
+
+
+/// Takes solutions in the form of a vector of 
+/// indices, and converts them each into a 
+/// Pentomino
+pub fn convertSolutions(board: &Pentomino,
+                        solutions: &Vec<Vec<uint>>, 
+                        placements: &Vec<Placement>, 
+                        offset: uint) -> Vec<Pentomino> {
+  let symbols = generateSymbols(offset);
+
+  solutions.iter().map(|s| {
+    convertSolution(board, s, placements, offset, &symbols)
+  }).collect()
 }
+
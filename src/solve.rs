@@ -1,31 +1,34 @@
-use std::uint;
-use std::bool;
+use rand::{Rng, IsaacRng, SeedableRng};
 use std::vec::Vec;
 use std::fmt::{Show, Formatter, Result};
+use collections::hashmap::HashSet;
+use time::precise_time_ns;
+use dlx::Matrix;
+use bitset::BitSet;
+use cancel::CancelToken;
+use checkpoint::{Checkpoint, Checkpointer};
+use estimate::Progress;
+use stats::Stats;
+use prune::Prune;
+use heuristic::{Heuristic, MinRemaining};
+use region::Splitter;
+use parse::Marks;
+use colour::Palette;
 use pentomino::Pentomino;
-use collections::dlist::DList;
-use collections::deque::Deque;
 
 
-/// Placements are represented in two ways.
-///
-/// First, as an array of integer values of
-/// the filled columns.
-/// Second, as an array of booleans representing
-/// which pieces on the board are filled 
-/// by the piece.
+/// A placement is represented as an array
+/// of integer values of the filled columns.
 pub struct Placement {
-  filled: Vec<uint>,
-  inner: Vec<bool>
+  filled: Vec<uint>
 }
 
 
 impl Placement {
-  fn new(filled: Vec<uint>, inner: Vec<bool>) -> Placement {
-    Placement { filled: filled, inner: inner }
+  fn new(filled: Vec<uint>) -> Placement {
+    Placement { filled: filled }
   }
   pub fn filled<'a>(&'a self) -> &'a Vec<uint> { &self.filled }
-  pub fn inner<'a>(&'a self) -> &'a Vec<bool> { &self.inner }
 }
 
 
@@ -38,241 +41,1002 @@ impl Eq for Placement {
 
 impl Show for Placement {
   fn fmt(&self, f: &mut Formatter) -> Result {
-    let mut buf = ~"";
-
-    for b in self.inner.iter() {
-      buf.push_str(bool::to_bit::<uint>(*b).to_str() + ", ");
-    }
-
-    write!(f.buf, "{:s}", buf) 
+    write!(f.buf, "{}", self.filled)
   }
 }
 
 
-/// Checks if a placement exists in a given array of Placements 
-fn permutationExists(p: &Placement, all: &Vec<Placement>) -> bool {
-  let mut present = false;
-
-  for o in all.iter() { if p == o { present = true; } }
-
-  present
+/// The columns of the placement matrix
+///
+///   * `active` - columns that at least one placement has a one in
+///   * `primary` - columns that every solution must cover (the squares 
+///                 of the board, and the mandatory pieces). The other
+///                 columns may be covered at most once.
+///   * `capacity` - number of times each column can be covered (the 
+///                  number of copies of each piece, one for squares)
+///   * `groups` - sets of columns, and the least and most times they
+///                can be covered between them
+///   * `lens` - number of placements with a one in each column
+pub struct Columns {
+  active: BitSet,
+  primary: BitSet,
+  capacity: Vec<uint>,
+  groups: Vec<(Vec<uint>, uint, uint)>,
+  lens: Vec<uint>
 }
 
 
-type MatrixColumn = (bool, uint);
+impl Columns {
+  fn new(cols: uint) -> Columns {
+    Columns { 
+      active: BitSet::new(cols), 
+      primary: BitSet::new(cols), 
+      capacity: Vec::from_elem(cols, 1 as uint),
+      groups: Vec::new(),
+      lens: Vec::from_elem(cols, 0 as uint) 
+    }
+  }
+  /// Number of columns
+  pub fn len(&self) -> uint { self.lens.len() }
+  /// Columns that can be covered by at least one placement
+  pub fn active<'a>(&'a self) -> &'a BitSet { &self.active }
+  /// Columns that must be covered
+  pub fn primary<'a>(&'a self) -> &'a BitSet { &self.primary }
+  /// Number of times column `c` can be covered
+  pub fn capacity(&self, c: uint) -> uint { *self.capacity.get(c) }
+  /// Requires the columns to be covered, between them, at 
+  /// least `min` and at most `max` times
+  pub fn addGroup(&mut self, columns: Vec<uint>, min: uint, max: uint) {
+    self.groups.push((columns, min, max));
+  }
+  /// Sets of columns, and the least and most times they can be 
+  /// covered between them
+  pub fn groups<'a>(&'a self) -> &'a Vec<(Vec<uint>, uint, uint)> { &self.groups }
+  /// Number of placements that cover column `c`
+  pub fn count(&self, c: uint) -> uint { *self.lens.get(c) }
+}
 
 
-trait Column {
-  fn len(&self) -> uint;
-  fn status(&self) -> bool;
-  fn incr(&mut self);
-  fn decr(&mut self);
-  fn toggle(&mut self, b: bool);
+/// A piece fixed in place: reflected over the X axis if `reflected`
+/// is set, then rotated right `turns` times, with its top left corner
+/// on the square `x`, `y` of the board
+pub struct Pin {
+  pub piece: uint,
+  pub turns: uint,
+  pub reflected: bool,
+  pub x: uint,
+  pub y: uint
 }
 
 
-impl Column for MatrixColumn {
-  fn status(&self) -> bool {
-    self.val0()
-  }
-  fn len(&self) -> uint {
-    self.val1()
-  }
-  fn incr(&mut self) {
-    let len = self.mut1();
-    *len = *len + 1;
+/// Finds the distinct ways a piece can be turned over (if 
+/// `useReflections`) and rotated (if `useRotations`). Turning
+/// a piece over shows the back of its squares in `palette`.
+pub fn orientations(piece: &Pentomino, palette: &Palette, useRotations: bool,
+                    useReflections: bool) -> Vec<Pentomino> {
+  let mut permutations = Vec::with_capacity(8);
+
+  // Add rotations
+  if useRotations {
+    for rotation in piece.rotations() { permutations.push(rotation); }
+  } else {
+    permutations.push(piece.clone());
   }
-  fn decr(&mut self) {
-    let len = self.mut1();
-    *len = *len - 1; 
+
+  // Add reflections
+  if useReflections {
+    let mut reflections = Vec::new();
+    for piece in permutations.iter() { reflections.push(palette.turnOver(piece)); }
+    permutations.push_all_move(reflections);
   }
-  fn toggle(&mut self, b: bool) {
-    let status = self.mut0();
-    *status = b; 
+
+  // Symmetric pieces have fewer distinct orientations. Each
+  // orientation is kept by its drawing, which gives its size
+  // and the colour of every square.
+  let mut distinct: Vec<Pentomino> = Vec::with_capacity(permutations.len());
+  let mut seen = HashSet::new();
+
+  for permutation in permutations.move_iter() {
+    if seen.insert(permutation.to_str()) { distinct.push(permutation); }
   }
+
+  distinct
 }
 
 
 /// Finds all placements of all pieces (does not add equivalent 
-/// placements) in all positions on the board.
+/// placements) in all positions on the board. The marks of each
+/// piece give how many copies of it there are, and whether every
+/// solution must use them. 
+///
+/// A pinned piece only has its pinned placement, and must be used. 
+/// No placement of a piece covers a square in its `forbidden` set.
+/// The squares of a piece only cover squares of the board with a
+/// matching colour in `palette`.
 pub fn generatePlacements(board: &Pentomino, 
                           pentominoes: &Vec<Pentomino>,
+                          marks: &Vec<Marks>,
+                          palette: &Palette,
+                          pins: &Vec<Pin>,
+                          forbidden: &Vec<BitSet>,
                           useRotations: bool,
-                          useReflections: bool) -> (Vec<MatrixColumn>, Vec<Placement>) {
+                          useReflections: bool) -> (Columns, Vec<Placement>) {
   let offset = pentominoes.len();
   let cols = board.area() + offset; 
   let mut placements = Vec::new();
-  let mut columns = Vec::from_elem(cols, (true, 0 as uint));
+  let mut columns = Columns::new(cols);
 
-  for (i, piece) in pentominoes.iter().enumerate() {
-    let mut count: uint = 0;
-    let mut permutations = Vec::with_capacity(8);
+  for (x, y, _) in board.filled() {
+    columns.primary.set(board.getIndex(x, y) + offset, true);
+  }
 
-    // Add rotations
-    if useRotations {
-      for rotation in piece.rotations() { permutations.push(rotation); }
-    } else {
-      permutations.push(piece.clone());
-    }
+  for (i, m) in marks.iter().enumerate() {
+    let pinned = pins.iter().any(|p| p.piece == i);
+    columns.primary.set(i, m.mandatory || pinned);
+    *columns.capacity.get_mut(i) = m.copies;
+  }
 
-    // Add reflections
-    if useReflections {
-      let mut reflections = Vec::new();
-      for piece in permutations.iter() { reflections.push(piece.reflectX()); }
-      permutations.push_all_move(reflections);
-    }
+  for (i, piece) in pentominoes.iter().enumerate() {
+    let mut count: uint = 0;
+    let mut seen = HashSet::new();
+    let pin = pins.iter().find(|p| p.piece == i);
+    let orientations = match pin {
+      Some(p) => Vec::from_elem(1, palette.orient(piece, p.turns, p.reflected)),
+      None => orientations(piece, palette, useRotations, useReflections)
+    };
 
     // Generate all placements for each piece
     for (x, y, _) in board.coordinates() {
-      for permutation in permutations.iter() {
-        if board.canPlace(permutation, x, y) {
-          let mut filled = Vec::with_capacity(permutation.size() + 1);;
-          let mut inner = Vec::from_elem(cols, false);
-          
-          *inner.get_mut(i) = true;
+      match pin {
+        Some(p) if p.x != x || p.y != y => continue,
+        _ => ()
+      }
+
+      for permutation in orientations.iter() {
+        if board.canPlace(permutation, x, y, palette) {
+          let cells: Vec<uint> = permutation.filled().map(|(x0, y0, _)| {
+            board.getIndex(x + x0, y + y0)
+          }).collect();
+
+          if cells.iter().any(|j| forbidden.get(i).get(*j)) { continue }
+
+          let mut filled = Vec::with_capacity(permutation.size() + 1);
+
           filled.push(i);
+          for j in cells.iter() { filled.push(*j + offset); }
 
-          for (x0, y0, _) in permutation.filled() {
-            let j = board.getIndex(x + x0, y + y0) + offset;
-            *inner.get_mut(j) = true;
-            filled.push(j);
-          }
+          // The same squares can still be reached from different
+          // orientations, so placements are kept by their columns
+          filled.mut_slice_from(1).sort();
 
-          let newPlacement = Placement::new(filled, inner);
+          if seen.contains(&filled) { continue }
 
-          if !permutationExists(&newPlacement, &placements) {
-            for i in newPlacement.filled.iter() {
-              columns.get_mut(*i).incr();
-            }
-            placements.push(newPlacement);
-            count += 1;
+          for j in filled.iter() {
+            *columns.lens.get_mut(*j) += 1;
+            columns.active.set(*j, true);
           }
+
+          seen.insert(filled.clone());
+          placements.push(Placement::new(filled));
+          count += 1;
         }
       }
     }
 
+    if pin.is_some() && count == 0 { 
+      fail!("pinned piece does not fit on the board!\n{:s}", piece.to_str()) 
+    }
+
     debug!("--");
     debug!("{:s}", piece.to_str());
     debug!("{:u} placements", count);
   }
 
-  for c in columns.mut_iter() { if c.len() == 0 { c.toggle(false); } }
-
   (columns, placements) 
 }
 
 
-/// Important parameters:
-///   * placements - a vector of ways to place each Pentomino on the board. 
-///   * columns - a vector of columns which hold the length of the number of 
-///               'active' filled spaces below it
-///   * rows - a boolean vector that keeps track of rows that still can be chosen.
-///            at each recursive call, rows that overlap with the chosen row 
-///            will be pruned.
+/// Removes the placements that are not marked to be kept, 
+/// and updates the column counts to match
+pub fn restrictPlacements(placements: Vec<Placement>, columns: &mut Columns,
+                          keep: &Vec<bool>) -> Vec<Placement> {
+  let mut kept = Vec::with_capacity(placements.len());
+
+  for (p, k) in placements.move_iter().zip(keep.iter()) {
+    if *k {
+      kept.push(p);
+    } else {
+      for i in p.filled.iter() { *columns.lens.get_mut(*i) -= 1; }
+    }
+  }
+
+  for i in range(0, columns.len()) {
+    if columns.count(i) == 0 { columns.active.set(i, false); }
+  }
+
+  kept
+}
+
+
+/// Puts the placements in a random order fixed by `seed`. The rows
+/// of each column are visited in the order they were added, so this
+/// changes which solutions a search finds first.
+pub fn shufflePlacements(placements: &mut Vec<Placement>, seed: u32) {
+  let mut rng: IsaacRng = SeedableRng::from_seed(&[seed]);
+  rng.shuffle_mut(placements.as_mut_slice());
+}
+
+
+/// How often, in nodes, the search checks whether it should stop
+static CHECK_INTERVAL: u64 = 1024;
+
+
+/// Limits on how much of the tree a search explores
+///
+///   * `maxSolutions` - stop after this many solutions (0 for no limit)
+///   * `cancel` - stop once this token is cancelled
+///   * `deadline` - stop once `precise_time_ns` passes this
+#[deriving(Clone)]
+pub struct Limits {
+  pub maxSolutions: uint,
+  pub cancel: Option<CancelToken>,
+  pub deadline: Option<u64>
+}
+
+
+impl Limits {
+  /// No limits other than a maximum number of solutions
+  pub fn new(maxSolutions: uint) -> Limits {
+    Limits { maxSolutions: maxSolutions, cancel: None, deadline: None }
+  }
+}
+
+
+/// The state of a search
+#[deriving(Eq, Clone, Show)]
+pub enum Status {
+  /// There may be more solutions to find
+  Searching,
+  /// Every branch of the tree was explored
+  Complete,
+  /// The search was cancelled through its CancelToken
+  Cancelled,
+  /// The search ran past its deadline
+  TimedOut
+}
+
+
+/// Possible results of going down the tree
+enum Step {
+  Solution,
+  DeadEnd,
+  Stopped
+}
+
+
+/// A level of the search: the column being branched on, the 
+/// node of the row currently chosen from it, how many rows
+/// of the column came before it, and how many rows were hidden
+/// before the level was entered
+struct Frame {
+  col: uint,
+  node: uint,
+  index: uint,
+  hidden: uint
+}
+
+
+/// A region of the matrix the search is covering before the rest:
+/// the headers of its columns, and the level it was chosen at
+struct Scope {
+  columns: BitSet,
+  depth: uint
+}
+
+
+/// Iterator over the solutions of a Dancing Links Matrix.
+///
+/// Attributes
+///   * matrix - the exact cover matrix, with the rows chosen so far 
+///              selected (and so removed from it) 
+///   * stack - the column and row chosen at each level of the search
+///   * current - the indices of the rows chosen so far
+///   * hidden - the nodes of the rows hidden while branching on 
+///              columns with bounds
+///   * started - whether the search has left the root yet
+///   * status - whether the search has finished, and why
+///   * found - number of solutions found so far
+///   * nodes - number of nodes of the tree visited so far
+///   * limits - when to stop searching early
+///   * checkpointer - saves the position of the search every so often
+///   * progress - prints the progress of the search every so often
+///   * stats - statistics collected about the search, if wanted
+///   * pruners - checks that cut branches with no solutions early
+///   * heuristic - picks the column to branch on at each node
+///   * splitter - splits the matrix into regions at each node, if the
+///                regions are to be solved separately
+///   * scopes - the regions being covered, innermost last
 /// 
 /// Algorithm
-/// 
-/// Placements hold a vector of integers representing filled coordinates on the board 
-/// (as well as which index the piece corresponds), and the actual vector of boolean 
-/// values that represent the placement of the piece on the board (an 8x8 board with 
-/// 12 pieces would have 64 + 12 columns).
 ///
-/// On each call, the column with the minimum length is selected. This column is chosen 
-/// to reduce the branching factor of the recursive calls.
+/// At each level, the uncovered primary column with the fewest rows is 
+/// chosen (unless another heuristic is given), to reduce the branching 
+/// factor of the search. If no primary
+/// column is left, every square on the board has been covered and 
+/// `current` is a solution, as long as every group of columns has
+/// been covered enough times.
 ///
-/// That column is removed, and a row with a one in that column is selected and removed.
-/// This action represents selecting one way to cover a certain square on the board, 
-/// or chosing a certain piece. Any row that has a one that overlaps with a one 
-/// in the chosen row can be removed because the two rows would represent an overlap 
-/// of two pieces on the board. 
+/// Each row in the chosen column represents one way to cover that square 
+/// on the board. Selecting a row covers every column it has a one in, 
+/// which unlinks every other row that overlaps it. Only the rows that 
+/// are still linked are visited, so no time is spent rescanning rows 
+/// that were already ruled out. Backtracking unselects the row, which 
+/// relinks everything in the opposite order, and moves on to the next 
+/// row in the column.
 ///
-/// Afterwards, the index of the chosen row is added to a vector, which keeps track of 
-/// the current solution. `solve` is then called recursively. This is done for each 
-/// row with a one in it from the chosen column.
-pub fn solve(placements: &mut Vec<Placement>, columns: &mut Vec<MatrixColumn>,
-             rows: &mut Vec<bool>, solutions: &mut uint, depth: uint,
-             current: &mut Vec<uint>, maxSolutions: uint, pieceNum: uint, 
-             success: &|&Vec<uint>| -> ()) {
-  if *solutions == maxSolutions && maxSolutions != 0 { return }
-
-  // Find the minimum column
-  let mut min = uint::MAX;
-
-  for (i, c) in columns.slice_from(pieceNum).iter().enumerate() {
-    if c.status() {
-      if min == uint::MAX { min = i + pieceNum }
-      if c.len() == 0 { return }
-      if c.len() < columns.get(min).len() { min = i + pieceNum; }
-    }
-  }
-
-  if min == uint::MAX {
-    *solutions += 1;
-    (*success)(current);
-    return;
-  }
-
-  // Remove the chosen column from the columns vector
-  columns.get_mut(min).toggle(false);
-
-  for row in range(0, rows.len()) {
-    // Choose a row that hasn't already been chosen
-    if *rows.get(row) && *placements.get(row).inner().get(min) {
-      // Store the columns and rows that were toggled, so it 
-      // can be undone afterwards
-      let mut toggledRows: DList<uint> = DList::new();
-      let mut toggledCols: DList<uint> = DList::new();
-
-      // Remove the overlapping rows
-      for c in placements.get(row).filled().iter() {
-        columns.get_mut(*c).toggle(false);
-
-        toggledCols.push_back(*c);
-
-        for row0 in range(0, rows.len()) {
-          if *rows.get(row0) && *placements.get(row0).inner().get(*c) {
-            *rows.get_mut(row0) = false;
-            for col0 in placements.get(row0).filled().iter() { 
-              columns.get_mut(*col0).decr(); 
-            }
-            toggledRows.push_back(row0);
-          }
+/// A column with bounds can be covered by more than one row, so it 
+/// is only branched on once the primary columns are covered, if it
+/// has not been covered enough times. Each row tried from it is
+/// hidden until the level is left, so the same set of rows is not
+/// found in more than one order.
+///
+/// If the regions are solved separately, the uncovered columns are
+/// split into regions that no row joins at each node. The search
+/// only branches on the columns of the first region until it has
+/// been covered, then moves on to the next, so each solution of the
+/// first region is followed by every solution of the rest, without
+/// keeping any of them. `count` multiplies the number of solutions
+/// of each region instead.
+///
+/// The levels are kept on an explicit stack instead of the call stack, 
+/// so the search can stop after each solution, and pick up where it
+/// left off on the next call to `next`. Each level chooses a different
+/// row, so the stack is allocated once, as deep as the matrix has
+/// rows, and boards with thousands of small pieces are searched 
+/// without allocating or running out of stack.
+pub struct Solutions {
+  matrix: Matrix,
+  stack: Vec<Frame>,
+  current: Vec<uint>,
+  hidden: Vec<uint>,
+  started: bool,
+  status: Status,
+  found: uint,
+  nodes: u64,
+  limits: Limits,
+  checkpointer: Option<Checkpointer>,
+  progress: Option<Progress>,
+  stats: Option<Stats>,
+  pruners: Vec<~Prune:Send>,
+  heuristic: ~Heuristic:Send,
+  splitter: Option<Splitter>,
+  scopes: Vec<Scope>
+}
+
+
+// Creation
+impl Solutions {
+  /// Creates an iterator over the solutions of `matrix` that
+  /// contain every row in `prefix`
+  pub fn new(matrix: Matrix, prefix: Vec<uint>, limits: Limits) -> Solutions {
+    let mut matrix = matrix;
+    let mut current = prefix;
+    let rows = matrix.numRows();
+
+    current.reserve(rows);
+
+    for r in current.iter() {
+      let n = matrix.rowNode(*r);
+      matrix.select(n);
+    }
+
+    Solutions {
+      matrix: matrix,
+      stack: Vec::with_capacity(rows),
+      current: current,
+      hidden: Vec::with_capacity(rows),
+      started: false,
+      status: Searching,
+      found: 0,
+      nodes: 0,
+      limits: limits,
+      checkpointer: None,
+      progress: None,
+      stats: None,
+      pruners: Vec::new(),
+      heuristic: ~MinRemaining as ~Heuristic:Send,
+      splitter: None,
+      scopes: Vec::new()
+    }
+  }
+  /// Picks the column to branch on with `heuristic`
+  pub fn chooseWith(&mut self, heuristic: ~Heuristic:Send) {
+    assert!(!self.started)
+    self.heuristic = heuristic;
+  }
+  /// Runs `pruner` at every node of the search
+  pub fn pruneWith(&mut self, pruner: ~Prune:Send) {
+    self.pruners.push(pruner);
+  }
+  /// Prints the progress of the search every so often
+  pub fn reportTo(&mut self, progress: Progress) {
+    self.progress = Some(progress);
+  }
+  /// Collects statistics about the search into `stats`
+  pub fn collectStats(&mut self, stats: Stats) {
+    self.stats = Some(stats);
+  }
+  /// Solves the regions the matrix splits into separately
+  pub fn splitRegions(&mut self) {
+    assert!(!self.started)
+    self.splitter = Some(Splitter::new(&self.matrix));
+  }
+  /// Saves a checkpoint every so often while searching
+  pub fn checkpointTo(&mut self, checkpointer: Checkpointer) {
+    self.checkpointer = Some(checkpointer);
+  }
+  /// Moves a new search to the position saved in a checkpoint, 
+  /// and picks up the count of solutions from there
+  pub fn resume(&mut self, checkpoint: &Checkpoint) {
+    assert!(!self.started)
+
+    for r in checkpoint.path.iter() {
+      let c = match self.branch() {
+        Some(c) => c,
+        None => fail!("checkpoint does not match the search tree!")
+      };
+
+      match self.matrix.nodeIn(*r, c) {
+        Some(n) => {
+          // Find how many rows of the column come before it
+          let mut index = 0;
+          let mut j = self.matrix.down(c);
+          while j != n { j = self.matrix.down(j); index += 1; }
+
+          let hidden = self.hidden.len();
+          self.enter(c, n, index, hidden)
         }
+        None => fail!("checkpoint does not match the search tree!")
       }
+    }
+
+    self.found = checkpoint.found;
+    self.started = checkpoint.advance;
+  }
+}
+
+
+// Getters
+impl Solutions {
+  /// Number of solutions found so far
+  pub fn found(&self) -> uint { self.found }
+  /// Number of nodes of the search tree visited so far
+  pub fn nodes(&self) -> u64 { self.nodes }
+  /// Whether the search has finished, and why
+  pub fn status(&self) -> Status { self.status }
+  /// Takes the statistics collected about the search
+  pub fn takeStats(&mut self) -> Option<Stats> { 
+    match self.stats {
+      Some(ref mut s) => s.finish(),
+      None => ()
+    }
+    self.stats.take() 
+  }
+  /// Estimates how much of the tree has been explored, from the
+  /// position of the row chosen at each level. Each level splits
+  /// the part of the tree left to it evenly between its rows.
+  pub fn fraction(&self) -> f64 {
+    if self.status == Complete { return 1.0 }
+
+    let mut fraction = 0.0;
+    let mut weight = 1.0;
+
+    for frame in self.stack.iter() {
+      let len = self.matrix.len(frame.col) as f64;
+      fraction += weight * frame.index as f64 / len;
+      weight /= len;
+    }
+
+    fraction
+  }
+  /// The row chosen at each level of the search
+  fn path(&self) -> Vec<uint> {
+    self.stack.iter().map(|f| self.matrix.row(f.node)).collect()
+  }
+}
+
 
-      // Recurse
-      current.push(row);
+// Checkpoints
+impl Solutions {
+  /// Saves the current position if a checkpoint is due, or if 
+  /// `force` is set. `advance` marks that the solution at the 
+  /// position has already been passed on.
+  fn saveCheckpoint(&mut self, advance: bool, force: bool) {
+    let due = match self.checkpointer {
+      Some(ref c) => force || c.due(),
+      None => false
+    };
 
-      solve(placements, columns, rows, solutions, depth + 1, 
-            current, maxSolutions, pieceNum, success); 
+    if !due { return }
 
-      current.pop();
+    let path = self.path();
+    let found = self.found;
+
+    match self.checkpointer {
+      Some(ref mut c) => c.save(found, advance, path),
+      None => ()
+    }
+  }
+  /// Marks the search as complete, removing its checkpoint
+  fn complete(&mut self) {
+    self.status = Complete;
+
+    match self.checkpointer {
+      Some(ref c) => c.finish(),
+      None => ()
+    }
+  }
+}
+
+
+// Progress
+impl Solutions {
+  /// Prints the progress of the search if an update is due
+  fn reportProgress(&mut self) {
+    let due = match self.progress {
+      Some(ref p) => p.due(),
+      None => false
+    };
+
+    if !due { return }
+
+    let (nodes, found, fraction) = (self.nodes, self.found, self.fraction());
+
+    match self.progress {
+      Some(ref mut p) => p.report(nodes, found, fraction),
+      None => ()
+    }
+  }
+}
 
-      // Restore the toggled rows and columns
-      for row0 in toggledRows.iter() { 
-        *rows.get_mut(*row0) = true; 
-        for col0 in placements.get(*row0).filled().iter() { 
-          columns.get_mut(*col0).incr(); 
+
+// Searching
+impl Solutions {
+  /// Selects the row containing node `n` from column `c`, 
+  /// going one level down the tree. If the column has bounds, the
+  /// row is hidden first, along with the rows before it that are 
+  /// not hidden yet. `hidden` is the number of rows that were 
+  /// hidden before the level was entered.
+  fn enter(&mut self, c: uint, n: uint, index: uint, hidden: uint) {
+    let r = self.matrix.row(n);
+
+    if self.matrix.capacity(c) > 1 {
+      loop {
+        let m = self.matrix.down(c);
+        self.matrix.hide(m);
+        self.hidden.push(m);
+        if m == n { break }
+      }
+    }
+
+    self.matrix.select(n);
+    self.current.push(r);
+    self.stack.push(Frame { col: c, node: n, index: index, hidden: hidden });
+
+    match self.stats {
+      Some(ref mut s) => s.choose(r),
+      None => ()
+    }
+  }
+  /// Unselects the row chosen at the deepest level, going one
+  /// level back up the tree. Returns the level that was left, or
+  /// None at the root.
+  fn leave(&mut self) -> Option<Frame> {
+    let frame = match self.stack.pop() {
+      Some(frame) => frame,
+      None => return None
+    };
+
+    // Regions chosen below the level no longer apply
+    loop {
+      match self.scopes.last() {
+        Some(s) if s.depth > self.stack.len() => (),
+        _ => break
+      }
+      self.scopes.pop();
+    }
+
+    self.matrix.unselect(frame.node);
+    self.current.pop();
+
+    match self.stats {
+      Some(ref mut s) => s.backtrack(),
+      None => ()
+    }
+
+    Some(frame)
+  }
+  /// The header of the column to branch on at the current node.
+  ///
+  /// If regions are solved separately, a new region is chosen once
+  /// the one being covered splits up or has been covered, and only
+  /// its columns are branched on. A column with no rows left is
+  /// still picked wherever it is, since the node is a dead end.
+  fn branch(&mut self) -> Option<uint> {
+    if self.splitter.is_none() { return self.heuristic.branch(&self.matrix) }
+
+    match self.matrix.choose() {
+      Some(c) if self.matrix.len(c) == 0 => return Some(c),
+      _ => ()
+    }
+
+    let depth = self.stack.len();
+    let live = match self.scopes.last() {
+      Some(s) => s.columns.iter().any(|h| self.matrix.remaining(h) > 0),
+      None => false
+    };
+
+    let regions = {
+      let within = if live { self.scopes.last().map(|s| &s.columns) } else { None };
+
+      match self.splitter {
+        Some(ref mut s) => s.regions(&self.matrix, within),
+        None => Vec::new()
+      }
+    };
+
+    if regions.len() == 0 { return self.heuristic.branch(&self.matrix) }
+
+    if !live || regions.len() > 1 {
+      let first = regions.move_iter().next().unwrap();
+      self.scopes.push(Scope { columns: first, depth: depth });
+    }
+
+    let columns = &self.scopes.last().unwrap().columns;
+    branchIn(&mut self.matrix, &self.heuristic, columns)
+  }
+  /// Counts a node, and every so often checks whether
+  /// the search has been cancelled or run out of time
+  fn interrupted(&mut self) -> bool {
+    self.nodes += 1;
+
+    if self.nodes % CHECK_INTERVAL != 0 { return false }
+
+    match self.limits.cancel {
+      Some(ref token) if token.isCancelled() => self.status = Cancelled,
+      _ => ()
+    }
+
+    match self.limits.deadline {
+      Some(deadline) if precise_time_ns() > deadline => self.status = TimedOut,
+      _ => ()
+    }
+
+    // Always save where the search stopped, so it can be resumed
+    let stopped = self.status != Searching;
+    self.saveCheckpoint(false, stopped);
+    self.reportProgress();
+
+    stopped
+  }
+  /// Moves on to the next row of the deepest column that has
+  /// rows left, backtracking out of exhausted columns. Returns 
+  /// false once every branch has been explored.
+  fn advance(&mut self) -> bool {
+    loop {
+      let frame = match self.leave() {
+        Some(frame) => frame,
+        None => return false
+      };
+
+      let next = self.matrix.down(frame.node);
+
+      if next != frame.col {
+        self.enter(frame.col, next, frame.index + 1, frame.hidden);
+        return true
+      }
+
+      // Bring back the rows hidden at this level
+      while self.hidden.len() > frame.hidden {
+        let m = self.hidden.pop().unwrap();
+        self.matrix.unhide(m);
+      }
+    }
+  }
+  /// Whether one of the pruners cuts the branch at the current node
+  fn pruned(&mut self) -> bool {
+    let matrix = &self.matrix;
+    self.pruners.mut_iter().any(|p| p.prune(matrix))
+  }
+  /// Goes down the tree taking the first row of each chosen
+  /// column, until it reaches a solution, or a column with 
+  /// no rows left, or the search is interrupted.
+  fn descend(&mut self) -> Step {
+    loop {
+      if self.interrupted() { return Stopped }
+
+      let depth = self.stack.len();
+
+      match self.stats {
+        Some(ref mut s) => s.visit(depth),
+        None => ()
+      }
+
+      // A group that needs more rows than it has left, or that
+      // is still below its minimum once nothing else can be 
+      // chosen, can not be kept within its bounds
+      let c = self.branch();
+      let blocked = self.matrix.blocked() || (c.is_none() && !self.matrix.satisfied());
+
+      if blocked {
+        match self.stats {
+          Some(ref mut s) => s.block(depth),
+          None => ()
+        }
+        return DeadEnd
+      }
+
+      // A column with no rows left can not be covered
+      let empty = match c {
+        Some(c) => self.matrix.len(c) == 0,
+        None => false
+      };
+
+      if empty {
+        match self.stats {
+          Some(ref mut s) => s.deadEnd(depth),
+          None => ()
+        }
+        return DeadEnd 
+      }
+
+      let c = match c {
+        Some(c) => c,
+        None => return Solution
+      };
+
+      if self.pruned() {
+        match self.stats {
+          Some(ref mut s) => s.prune(depth),
+          None => ()
+        }
+        return DeadEnd
+      }
+
+      let n = self.matrix.down(c);
+      let hidden = self.hidden.len();
+      self.enter(c, n, 0, hidden);
+    }
+  }
+}
+
+
+impl Solutions {
+  /// Passes each remaining solution to `success` until it returns 
+  /// false. Returns the number of solutions found, and the state 
+  /// the search stopped in.
+  pub fn run(&mut self, success: &|&Vec<uint>| -> bool) -> (uint, Status) {
+    loop {
+      let solution = match self.next() {
+        Some(s) => s,
+        None => break
+      };
+
+      if !(*success)(&solution) { 
+        // Save where the search stopped, so it can be resumed
+        self.saveCheckpoint(true, true);
+        break 
+      } 
+    }
+
+    self.finishProgress();
+    (self.found, self.status)
+  }
+  /// Counts the solutions, multiplying the number of solutions of
+  /// each region if the regions are solved separately. Returns the
+  /// number of solutions, and the state the search stopped in.
+  ///
+  /// Counting goes down the tree on the call stack, since each
+  /// level has to add up the count of each of its rows.
+  pub fn count(&mut self) -> (uint, Status) {
+    assert!(!self.started)
+    self.started = true;
+
+    let found = self.countIn(None);
+
+    if self.status == Searching { self.complete(); }
+
+    self.found = found;
+    self.finishProgress();
+    (self.found, self.status)
+  }
+  /// Counts the solutions below the current node that cover the
+  /// columns in `within`, or every column
+  fn countIn(&mut self, within: Option<&BitSet>) -> uint {
+    if self.interrupted() { return 0 }
+
+    let depth = self.stack.len();
+
+    match self.stats {
+      Some(ref mut s) => s.visit(depth),
+      None => ()
+    }
+
+    if self.matrix.blocked() {
+      match self.stats {
+        Some(ref mut s) => s.block(depth),
+        None => ()
+      }
+      return 0
+    }
+
+    let regions = match self.splitter {
+      Some(ref mut s) => s.regions(&self.matrix, within),
+      None => Vec::new()
+    };
+
+    // No row joins two regions, so every solution of each
+    // region goes with every solution of the others
+    if regions.len() > 1 {
+      let mut product = 1;
+
+      for region in regions.iter() {
+        product *= self.countIn(Some(region));
+        if product == 0 || self.status != Searching { break }
+      }
+
+      return product
+    }
+
+    let c = match within {
+      Some(_) if regions.len() == 0 => return 1,
+      Some(columns) => branchIn(&mut self.matrix, &self.heuristic, columns),
+      None => self.heuristic.branch(&self.matrix)
+    };
+
+    let c = match c {
+      Some(c) => c,
+      None if self.matrix.satisfied() => return 1,
+      None => {
+        match self.stats {
+          Some(ref mut s) => s.block(depth),
+          None => ()
         }
+        return 0
+      }
+    };
+
+    if self.matrix.len(c) == 0 {
+      match self.stats {
+        Some(ref mut s) => s.deadEnd(depth),
+        None => ()
+      }
+      return 0
+    }
+
+    if self.pruned() {
+      match self.stats {
+        Some(ref mut s) => s.prune(depth),
+        None => ()
       }
-      for col in toggledCols.iter() { columns.get_mut(*col).toggle(true); }
+      return 0
     }
+
+    let hidden = self.hidden.len();
+    let mut total = 0;
+    let mut n = self.matrix.down(c);
+    let mut index = 0;
+
+    while n != c {
+      self.enter(c, n, index, hidden);
+      total += self.countIn(within);
+      self.leave();
+
+      if self.status != Searching { break }
+
+      n = self.matrix.down(n);
+      index += 1;
+    }
+
+    // Bring back the rows hidden at this level
+    while self.hidden.len() > hidden {
+      let m = self.hidden.pop().unwrap();
+      self.matrix.unhide(m);
+    }
+
+    total
   }
+  /// Leaves the final count on the progress line
+  fn finishProgress(&mut self) {
+    let (nodes, found, fraction) = (self.nodes, self.found, self.fraction());
+
+    match self.progress {
+      Some(ref mut p) => { p.report(nodes, found, fraction); p.finish() }
+      None => ()
+    }
+  }
+}
 
-  // Restore the minimum column to the columns vector
-  columns.get_mut(min).toggle(true);
+
+/// Picks the column to branch on with `heuristic`, out of the
+/// columns whose headers are in `columns`
+fn branchIn(matrix: &mut Matrix, heuristic: &~Heuristic:Send, columns: &BitSet) -> Option<uint> {
+  let unlinked = matrix.focus(columns);
+  let c = heuristic.branch(&*matrix);
+  matrix.unfocus(&unlinked);
+  c
 }
 
 
-/// For debug
-fn printMatrix(placements: &Vec<Placement>, columns: &Vec<MatrixColumn>,
-               filled: &Vec<bool>) {
-  for (i, p) in placements.iter().enumerate() {
-    if *filled.get(i) {
-      for (j, b) in p.inner.iter().enumerate() { 
-        if columns.get(j).status() { print!("{:u}, ", bool::to_bit::<uint>(*b)) }
+impl Iterator<Vec<uint>> for Solutions {
+  fn next(&mut self) -> Option<Vec<uint>> {
+    if self.status != Searching { return None }
+    if self.found == self.limits.maxSolutions && self.limits.maxSolutions != 0 { 
+      // Save where the search stopped, so it can be resumed
+      self.saveCheckpoint(true, true);
+      return None 
+    }
+
+    if self.started {
+      // The previous solution has been passed on, so if a checkpoint
+      // is due the search can be resumed from after it. Solutions
+      // passed on after the last checkpoint are found again when
+      // resuming, so it also keeps the length of the output.
+      self.saveCheckpoint(true, false);
+
+      // Backtrack out of the previous solution
+      if !self.advance() {
+        self.complete();
+        return None
       }
-      println!("")
     }
+
+    self.started = true;
+
+    loop {
+      match self.descend() {
+        Solution => break,
+        Stopped => return None,
+        DeadEnd => if !self.advance() {
+          self.complete();
+          return None
+        }
+      }
+    }
+
+    self.found += 1;
+
+    Some(self.current.clone())
+  }
+}
+
+
+/// Builds a Dancing Links matrix out of the placements. The columns
+/// of optional pieces are secondary, so a solution does not need to
+/// use them. Copies of a piece share a column, so solutions that only
+/// swap copies around are the same set of rows.
+pub fn buildMatrix(placements: &Vec<Placement>, columns: &Columns) -> Matrix {
+  let mut matrix = Matrix::new(columns.primary());
+
+  for c in range(0, columns.len()) {
+    if columns.capacity(c) > 1 { matrix.setCapacity(c, columns.capacity(c)); }
   }
+
+  for &(ref group, min, max) in columns.groups.iter() {
+    matrix.addGroup(group, min, max);
+  }
+
+  for p in placements.iter() { matrix.addRow(p.filled()); }
+
+  matrix
+}
+
+
+/// Finds every way to cover the board with the placements, passing
+/// each one to `success` until it returns false. Returns the number 
+/// of solutions found, and the state the search stopped in.
+pub fn solve(placements: &Vec<Placement>, columns: &Columns, limits: Limits,
+             success: &|&Vec<uint>| -> bool) -> (uint, Status) {
+  let matrix = buildMatrix(placements, columns);
+  Solutions::new(matrix, Vec::new(), limits).run(success)
 }
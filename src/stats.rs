@@ -0,0 +1,168 @@
+use std::vec::Vec;
+use std::io::File;
+use time::precise_time_ns;
+
+
+/// Counts of what a search did at one depth of the tree
+///
+///   * `nodes` - number of nodes visited at the depth
+///   * `deadEnds` - nodes left because a column had no rows
+///   * `blocked` - nodes left because a group could no longer be
+///                 given a number of rows within its bounds
+///   * `pruned` - nodes left because a pruner cut the branch
+///   * `time` - nanoseconds spent at nodes of the depth
+#[deriving(Clone)]
+pub struct Level {
+  pub nodes: u64,
+  pub deadEnds: u64,
+  pub blocked: u64,
+  pub pruned: u64,
+  pub time: u64
+}
+
+
+/// Statistics collected while searching, to compare how hard
+/// puzzles are, and how changes to the solver affect the search
+///
+/// Attributes
+///   * levels - counts for each depth of the tree
+///   * backtracks - number of times a chosen row was unselected
+///   * chosen - number of times a row of each piece was chosen
+///   * pieceOf - the piece that each row places
+///   * depth - the depth of the node visited last
+///   * last - when the node visited last was reached
+///
+pub struct Stats {
+  pub levels: Vec<Level>,
+  pub backtracks: u64,
+  pub chosen: Vec<u64>,
+  pieceOf: Vec<uint>,
+  depth: uint,
+  last: u64
+}
+
+
+// Creation
+impl Stats {
+  /// Creates empty statistics for a matrix where row `r`
+  /// places piece `pieceOf[r]`, out of `pieces` pieces
+  pub fn new(pieceOf: Vec<uint>, pieces: uint) -> Stats {
+    Stats {
+      levels: Vec::new(),
+      backtracks: 0,
+      chosen: Vec::from_elem(pieces, 0u64),
+      pieceOf: pieceOf,
+      depth: 0,
+      last: precise_time_ns()
+    }
+  }
+}
+
+
+// Recording
+impl Stats {
+  /// Adds the time since the last node to the depth it was at
+  fn lap(&mut self) {
+    let now = precise_time_ns();
+
+    if self.depth < self.levels.len() {
+      self.levels.get_mut(self.depth).time += now - self.last;
+    }
+
+    self.last = now;
+  }
+  /// Records a visit to a node at `depth`
+  pub fn visit(&mut self, depth: uint) {
+    self.lap();
+
+    while self.levels.len() <= depth {
+      self.levels.push(Level { nodes: 0, deadEnds: 0, blocked: 0, pruned: 0, time: 0 });
+    }
+
+    self.levels.get_mut(depth).nodes += 1;
+    self.depth = depth;
+  }
+  /// Records that the node at `depth` has a column with no rows
+  pub fn deadEnd(&mut self, depth: uint) {
+    self.levels.get_mut(depth).deadEnds += 1;
+  }
+  /// Records that a group can not be kept within its
+  /// bounds from the node at `depth`
+  pub fn block(&mut self, depth: uint) {
+    self.levels.get_mut(depth).blocked += 1;
+  }
+  /// Records that a pruner cut the branch at `depth`
+  pub fn prune(&mut self, depth: uint) {
+    self.levels.get_mut(depth).pruned += 1;
+  }
+  /// Records that row `r` was chosen
+  pub fn choose(&mut self, r: uint) {
+    *self.chosen.get_mut(*self.pieceOf.get(r)) += 1;
+  }
+  /// Records that a chosen row was unselected
+  pub fn backtrack(&mut self) {
+    self.backtracks += 1;
+  }
+  /// Adds the time spent at the last node, once the search stops
+  pub fn finish(&mut self) {
+    self.lap();
+  }
+}
+
+
+// Output
+impl Stats {
+  /// Total number of nodes visited
+  pub fn nodes(&self) -> u64 {
+    self.levels.iter().fold(0, |a, l| a + l.nodes)
+  }
+  /// Total number of dead ends
+  pub fn deadEnds(&self) -> u64 {
+    self.levels.iter().fold(0, |a, l| a + l.deadEnds)
+  }
+  /// Total number of nodes blocked by groups
+  pub fn blocked(&self) -> u64 {
+    self.levels.iter().fold(0, |a, l| a + l.blocked)
+  }
+  /// Total number of branches cut by pruners
+  pub fn pruned(&self) -> u64 {
+    self.levels.iter().fold(0, |a, l| a + l.pruned)
+  }
+  /// Formats the statistics as a JSON object, along with the
+  /// number of solutions found and the state the search ended in
+  pub fn toJson(&self, solutions: uint, status: &str) -> ~str {
+    let mut buf = ~"{\n";
+
+    buf.push_str(format!("  \"status\": \"{:s}\",\n", status));
+    buf.push_str(format!("  \"solutions\": {:u},\n", solutions));
+    buf.push_str(format!("  \"nodes\": {},\n", self.nodes()));
+    buf.push_str(format!("  \"backtracks\": {},\n", self.backtracks));
+    buf.push_str(format!("  \"deadEnds\": {},\n", self.deadEnds()));
+    buf.push_str(format!("  \"blocked\": {},\n", self.blocked()));
+    buf.push_str(format!("  \"pruned\": {},\n", self.pruned()));
+    buf.push_str("  \"levels\": [");
+
+    for (depth, l) in self.levels.iter().enumerate() {
+      if depth > 0 { buf.push_str(","); }
+      buf.push_str(format!("\n    \\{ \"depth\": {:u}, \"nodes\": {}, \"deadEnds\": {}, \"blocked\": {}, \"pruned\": {}, \"time\": {} \\}",
+                           depth, l.nodes, l.deadEnds, l.blocked, l.pruned, l.time));
+    }
+
+    buf.push_str("\n  ],\n  \"pieces\": [");
+
+    for (piece, n) in self.chosen.iter().enumerate() {
+      if piece > 0 { buf.push_str(","); }
+      buf.push_str(format!("\n    \\{ \"piece\": {:u}, \"chosen\": {} \\}", piece, *n));
+    }
+
+    buf.push_str("\n  ]\n}\n");
+    buf
+  }
+  /// Writes the statistics as JSON to a file
+  pub fn save(&self, path: &Path, solutions: uint, status: &str) {
+    match File::create(path).write_str(self.toJson(solutions, status)) {
+      Ok(_) => (),
+      Err(e) => fail!("stats file error: {}", e)
+    }
+  }
+}
@@ -0,0 +1,228 @@
+use std::mem;
+use std::vec::Vec;
+use collections::hashmap::HashMap;
+use solve::Placement;
+use pentomino::{Pentomino, Filled, Empty};
+use colour::Palette;
+
+
+/// Maps a coordinate on a `dimX` x `dimY` board to its image
+/// under one of the 8 rotations and reflections of a square
+fn transform(t: uint, x: uint, y: uint, dimX: uint, dimY: uint) -> (uint, uint) {
+  match t {
+    0 => (x, y),
+    1 => (dimY - y - 1, x),
+    2 => (dimX - x - 1, dimY - y - 1),
+    3 => (y, dimX - x - 1),
+    4 => (dimX - x - 1, y),
+    5 => (x, dimY - y - 1),
+    6 => (y, x),
+    7 => (dimY - y - 1, dimX - x - 1),
+    _ => fail!("no transformation {:u}", t)
+  }
+}
+
+
+/// Sorts the columns a placement fills, so placements 
+/// can be looked up by the squares they cover
+fn key(filled: &Vec<uint>) -> Vec<uint> {
+  let mut key = filled.clone();
+  key.as_mut_slice().sort();
+  key
+}
+
+
+/// Maps the columns a placement fills to their images under
+/// a symmetry
+fn image(forward: &Vec<uint>, filled: &Vec<uint>, offset: uint) -> Vec<uint> {
+  key(&filled.iter().map(|c| {
+    if *c < offset { *c } else { *forward.get(*c - offset) + offset }
+  }).collect())
+}
+
+
+/// Inverts a permutation
+fn invert(perm: &Vec<uint>) -> Vec<uint> {
+  let mut inverse = Vec::from_elem(perm.len(), 0 as uint);
+  for (j, i) in perm.iter().enumerate() { *inverse.get_mut(*i) = j; }
+  inverse
+}
+
+
+/// The rotations and reflections that map a board onto itself
+///
+/// Attributes
+///   * perms - for each symmetry other than the identity, the
+///             index of the square that is moved onto each square
+///             of the board
+///
+pub struct Symmetry {
+  perms: Vec<Vec<uint>>
+}
+
+
+// Creation
+impl Symmetry {
+  /// Finds every rotation and reflection that maps each filled
+  /// square of the board onto a filled square, of the same colour
+  /// if the puzzle has a colour legend. Without one, the characters
+  /// the board is drawn with are not compared, since `restrictTo`
+  /// drops the symmetries the placements do not follow anyway.
+  pub fn new(board: &Pentomino, palette: &Palette) -> Symmetry {
+    let mut perms = Vec::new();
+
+    for t in range(1u, 8) {
+      // Quarter turns and diagonal reflections only
+      // fit on square boards
+      if board.dimX != board.dimY && (t % 2 == 1 && t < 4 || t > 5) { continue }
+
+      let mut perm = Vec::from_elem(board.area(), 0 as uint);
+      let mut fits = true;
+
+      for i in board.range() {
+        let (x, y) = board.getCoordinates(i);
+        let (x0, y0) = transform(t, x, y, board.dimX, board.dimY);
+        let j = board.getIndex(x0, y0);
+
+        let same = match (board.get(x, y), board.get(x0, y0)) {
+          (&Filled(a), &Filled(b)) => !palette.isActive() || palette.sameColour(a, b),
+          (&Empty, &Empty) => true,
+          _ => false
+        };
+
+        if !same { fits = false; break }
+
+        *perm.get_mut(j) = i;
+      }
+
+      if fits { perms.push(perm); }
+    }
+
+    Symmetry { perms: perms }
+  }
+}
+
+
+impl Symmetry {
+  /// Number of symmetries of the board, including the identity
+  pub fn order(&self) -> uint {
+    self.perms.len() + 1
+  }
+  /// The placements of a solution, as the sorted columns each one
+  /// fills, in order. Copies of a piece are told apart by the squares
+  /// they cover, so solutions that only differ in how copies of a
+  /// piece are laid out are not the same.
+  fn form(&self, solution: &Vec<uint>, placements: &Vec<Placement>) -> Vec<Vec<uint>> {
+    let mut form: Vec<Vec<uint>> = solution.iter().map(|n| {
+      key(placements.get(*n).filled())
+    }).collect();
+
+    form.as_mut_slice().sort();
+    form
+  }
+  /// The image of a solution in `form` under a symmetry
+  fn imageOf(&self, forward: &Vec<uint>, form: &Vec<Vec<uint>>,
+             offset: uint) -> Vec<Vec<uint>> {
+    let mut image: Vec<Vec<uint>> = form.iter().map(|p| image(forward, p, offset)).collect();
+    image.as_mut_slice().sort();
+    image
+  }
+  /// The form of a solution that comes first, in lexicographic order,
+  /// among its images under the symmetries of the board. Isometric
+  /// solutions have the same canonical form.
+  pub fn canonical(&self, solution: &Vec<uint>, placements: &Vec<Placement>,
+                   offset: uint) -> Vec<Vec<uint>> {
+    let form = self.form(solution, placements);
+    let mut best = form.clone();
+
+    for perm in self.perms.iter() {
+      let image = self.imageOf(&invert(perm), &form, offset);
+      if image < best { best = image; }
+    }
+
+    best
+  }
+  /// Whether a solution comes first, in lexicographic order, among
+  /// all of its images under the symmetries of the board. Exactly
+  /// one solution in each class of isometric solutions is canonical.
+  pub fn isCanonical(&self, solution: &Vec<uint>, placements: &Vec<Placement>,
+                     offset: uint) -> bool {
+    let form = self.form(solution, placements);
+
+    self.perms.iter().all(|perm| !(self.imageOf(&invert(perm), &form, offset) < form))
+  }
+}
+
+
+// Symmetry breaking
+impl Symmetry {
+  /// Drops the symmetries that map some placement to a position, or 
+  /// an orientation, that is not one of the placements (for example 
+  /// when reflections are turned off). Returns true if every symmetry
+  /// of the board was kept.
+  pub fn restrictTo(&mut self, placements: &Vec<Placement>, offset: uint) -> bool {
+    let mut index = HashMap::new();
+
+    for p in placements.iter() { index.insert(key(p.filled()), ()); }
+
+    let before = self.perms.len();
+    let perms: Vec<Vec<uint>> = mem::replace(&mut self.perms, Vec::new()).move_iter().filter(|perm| {
+      let forward = invert(perm);
+      placements.iter().all(|p| index.contains_key(&image(&forward, p.filled(), offset)))
+    }).collect();
+
+    self.perms = perms;
+    self.perms.len() == before
+  }
+  /// Picks a piece, out of the `candidates` that are used in every 
+  /// solution, and keeps only one placement of it from each set of 
+  /// placements that the symmetries map onto each other. Every class 
+  /// of isometric solutions then has exactly one solution left.
+  ///
+  /// This only works if no symmetry maps a placement of the piece
+  /// onto itself, so pieces that are symmetric themselves can not be
+  /// used. Returns the piece, and which placements to keep, or None 
+  /// if there is no such piece.
+  pub fn breakSymmetry(&self, placements: &Vec<Placement>, offset: uint,
+                       candidates: &Vec<bool>) -> Option<(uint, Vec<bool>)> {
+    if self.perms.len() == 0 { return None }
+
+    let mut index = HashMap::new();
+
+    for (i, p) in placements.iter().enumerate() { index.insert(key(p.filled()), i); }
+
+    let forward: Vec<Vec<uint>> = self.perms.iter().map(|perm| invert(perm)).collect();
+    let mut best = None;
+    let mut bestCount = 0;
+
+    'pieces: for piece in range(0, offset) {
+      if !*candidates.get(piece) { continue }
+
+      let mut keep = Vec::from_elem(placements.len(), true);
+      let mut count = 0;
+
+      for (i, p) in placements.iter().enumerate() {
+        if *p.filled().get(0) != piece { continue }
+
+        let mut min = i;
+
+        for f in forward.iter() {
+          let j = *index.get(&image(f, p.filled(), offset));
+          if j == i { continue 'pieces }
+          if j < min { min = j }
+        }
+
+        *keep.get_mut(i) = min == i;
+        count += 1;
+      }
+
+      // Prefer the piece that removes the most placements
+      if count > bestCount {
+        best = Some((piece, keep));
+        bestCount = count;
+      }
+    }
+
+    best
+  }
+}
@@ -0,0 +1,140 @@
+"""
+Compares the solver against an older build of it (the baseline
+commit by default) on every puzzle in test/ that both can read.
+
+For each puzzle both builds are run with --all-solutions, and the
+solutions they write are compared as sets. Pieces are drawn with
+random letters, so each solution is relabelled by the order its
+letters first appear in before comparing. The number of distinct
+solutions (without --all-solutions) is compared as well.
+
+Then both builds are timed on the 6x10 board.
+
+usage: python test/regress.py [baseline commit] [timeout seconds]
+"""
+
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+import time
+
+ROOT = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+BASELINE = 'bdd2900'
+TIMEOUT = 600
+TIMED = 'pentominoes6x10.txt'
+RUNS = 3
+
+
+def build(directory):
+    if not os.path.isdir(os.path.join(directory, 'build')):
+        os.mkdir(os.path.join(directory, 'build'))
+    subprocess.check_call(['make', '-C', directory])
+    return os.path.join(directory, 'build', 'main')
+
+
+def checkout(commit, directory):
+    archive = subprocess.Popen(['git', '-C', ROOT, 'archive', commit],
+                               stdout=subprocess.PIPE)
+    subprocess.check_call(['tar', '-x', '-C', directory], stdin=archive.stdout)
+    archive.wait()
+
+
+def relabel(lines):
+    names = {}
+    out = []
+    for line in lines:
+        row = ''
+        for c in line:
+            if c == ' ':
+                row += c
+            else:
+                if c not in names:
+                    names[c] = chr(ord('a') + len(names))
+                row += names[c]
+        out.append(row.rstrip())
+    return '\n'.join(out)
+
+
+def read_solutions(path):
+    solutions = []
+    buf = None
+    with open(path) as f:
+        for line in f.read().split('\n'):
+            if line.startswith('-- Solution'):
+                buf = []
+            elif buf is not None:
+                if line == '':
+                    solutions.append(relabel(buf))
+                    buf = None
+                else:
+                    buf.append(line)
+    return solutions
+
+
+def run(binary, puzzle, options, timeout):
+    fd, out = tempfile.mkstemp()
+    os.close(fd)
+    try:
+        subprocess.run([binary, puzzle, '--output=' + out] + options,
+                       stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+                       timeout=timeout, check=True)
+        return read_solutions(out)
+    finally:
+        if os.path.exists(out):
+            os.remove(out)
+
+
+def main():
+    baseline = sys.argv[1] if len(sys.argv) > 1 else BASELINE
+    timeout = int(sys.argv[2]) if len(sys.argv) > 2 else TIMEOUT
+    work = tempfile.mkdtemp()
+    failed = False
+
+    try:
+        checkout(baseline, work)
+        old = build(work)
+        new = build(ROOT)
+
+        puzzles = sorted(f for f in os.listdir(os.path.join(work, 'test'))
+                         if f.endswith('.txt'))
+
+        for name in puzzles:
+            puzzle = os.path.join(ROOT, 'test', name)
+            try:
+                a = run(old, puzzle, ['--all-solutions'], timeout)
+                b = run(new, puzzle, ['--all-solutions'], timeout)
+                da = len(run(old, puzzle, [], timeout))
+                db = len(run(new, puzzle, [], timeout))
+            except subprocess.TimeoutExpired:
+                print('%-50s skipped (over %d seconds)' % (name, timeout))
+                continue
+            except subprocess.CalledProcessError as e:
+                print('%-50s FAILED (exit status %d)' % (name, e.returncode))
+                failed = True
+                continue
+
+            same = sorted(a) == sorted(b) and da == db
+            failed = failed or not same
+            print('%-50s %s (%d solutions, %d distinct; now %d, %d distinct)' %
+                  (name, 'ok' if same else 'DIFFERENT', len(a), da, len(b), db))
+
+        puzzle = os.path.join(ROOT, 'test', TIMED)
+
+        for label, binary in (('baseline', old), ('current', new)):
+            best = None
+            for _ in range(RUNS):
+                start = time.time()
+                run(binary, puzzle, [], None)
+                elapsed = time.time() - start
+                best = elapsed if best is None else min(best, elapsed)
+            print('%s %s: %.2f seconds (best of %d)' % (TIMED, label, best, RUNS))
+    finally:
+        shutil.rmtree(work)
+
+    sys.exit(1 if failed else 0)
+
+
+if __name__ == '__main__':
+    main()